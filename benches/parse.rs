@@ -0,0 +1,66 @@
+//! compares the parsing of a large `clientlist` answer into StringMaps and RecordRefs.
+
+#[macro_use]
+extern crate criterion;
+extern crate sqlib;
+
+use criterion::Criterion;
+use sqlib::escaping::unescape;
+use sqlib::map::{to_map, RecordRef};
+use sqlib::response::records;
+use std::hint::black_box;
+
+// creates the answer of `clientlist -uid -away -voice -times -groups -info -country -ip` for a
+// server with the given number of clients.
+fn clientlist(clients: usize) -> String {
+    (0..clients)
+        .map(|i| {
+            format!(
+                "clid={i} cid={cid} client_database_id={i} client_nickname=John\\sDoe\\s{i} \
+                 client_type=0 client_unique_identifier=P{i:027}= client_away=0 \
+                 client_away_message client_flag_talking=0 client_input_muted=0 \
+                 client_output_muted=0 client_input_hardware=1 client_output_hardware=1 \
+                 client_talk_power=75 client_is_talker=0 client_is_priority_speaker=0 \
+                 client_is_recording=0 client_is_channel_commander=0 client_idle_time=1234 \
+                 client_created=1500000000 client_lastconnected=1600000000 \
+                 client_servergroups=6,8 client_channel_group_id=8 \
+                 client_channel_group_inherited_channel_id={cid} \
+                 client_version=3.5.6\\s[Build:\\s1606312422] client_platform=Windows \
+                 client_country=DE connection_client_ip=192.168.0.{ip}",
+                i = i,
+                cid = i % 20,
+                ip = i % 255
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+fn parse(c: &mut Criterion) {
+    let answer = clientlist(500);
+
+    c.bench_function("clientlist 500 to_map", |b| {
+        b.iter(|| {
+            let maps: Vec<_> = records(black_box(&answer)).map(to_map).collect();
+            let nicknames: Vec<String> = maps
+                .iter()
+                .map(|map| unescape(&map["client_nickname"]))
+                .collect();
+            black_box(nicknames)
+        })
+    });
+
+    c.bench_function("clientlist 500 RecordRef", |b| {
+        b.iter(|| {
+            let records = RecordRef::parse_list(black_box(&answer));
+            let nicknames: Vec<_> = records
+                .iter()
+                .map(|record| record.get("client_nickname"))
+                .collect();
+            black_box(nicknames)
+        })
+    });
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
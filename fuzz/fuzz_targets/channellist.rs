@@ -0,0 +1,11 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate sqlib;
+
+use sqlib::channel::ChannelList;
+use std::str::FromStr;
+
+fuzz_target!(|s: &str| {
+    let _ = ChannelList::from_str(s);
+});
@@ -0,0 +1,11 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate sqlib;
+
+use sqlib::client::ClientList;
+use std::str::FromStr;
+
+fuzz_target!(|s: &str| {
+    let _ = ClientList::from_str(s);
+});
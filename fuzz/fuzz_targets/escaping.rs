@@ -0,0 +1,11 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate sqlib;
+
+use sqlib::escaping::{escape, unescape};
+
+fuzz_target!(|s: &str| {
+    assert_eq!(unescape(&escape(s)), s);
+    unescape(s);
+});
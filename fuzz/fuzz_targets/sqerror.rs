@@ -0,0 +1,13 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate sqlib;
+
+use sqlib::error::SQError;
+
+fuzz_target!(|s: &str| {
+    if let Some(err) = SQError::parse(s) {
+        assert!(s.starts_with("error "));
+        assert_eq!(SQError::parse_is_ok(s).is_ok(), err == SQError::ok());
+    }
+});
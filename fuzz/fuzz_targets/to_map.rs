@@ -0,0 +1,15 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate sqlib;
+
+use sqlib::map::{to_map, Record};
+
+fuzz_target!(|s: &str| {
+    let map = to_map(s);
+    let record = Record::parse(s);
+    assert!(map.len() <= record.len());
+    // a parsed record is written without the superfluous whitespace
+    assert_eq!(Record::parse(&record.to_string()), record);
+    Record::parse_list(s);
+});
@@ -0,0 +1,255 @@
+//! sqlib-derive provides the `FromStringMap` derive macro for sqlib.
+//!
+//! The macro implements `sqlib::map::FromStringMap` and `sqlib::map::ToParams` for a struct with
+//! named fields. Every field is read from (and written to) the key with the same name. The
+//! strict `try_from_map` and `try_mut_from_map` return an error for values, that can not be
+//! parsed, while `from_map` and `mut_from_map` leave those fields unchanged. The
+//! behaviour can be changed with `#[sqlib(...)]` field attributes:
+//!
+//! - `rename = "key"` uses another key than the field name.
+//! - `escape` unescapes the value when reading and escapes it when writing.
+//! - `default = "path::to::function"` uses the function instead of `Default::default()` for
+//!   `from_map`.
+//! - `skip` neither reads nor writes the field.
+//!
+//! Fields of the type `Option<T>` are optional: they are `None` if the key is missing and they
+//! are left out of `to_params` if they are `None`.
+//!
+//! # Example
+//! ```ignore
+//! #[macro_use]
+//! extern crate sqlib;
+//!
+//! use sqlib::map::{to_map, FromStringMap, ToParams};
+//!
+//! #[derive(FromStringMap)]
+//! struct Ban {
+//!     banid: i64,
+//!     #[sqlib(escape)]
+//!     reason: String,
+//!     #[sqlib(rename = "lastnickname", escape)]
+//!     nickname: Option<String>,
+//! }
+//!
+//! let ban = Ban::from_map(&to_map("banid=1 reason=too\\sloud"));
+//! assert_eq!(ban.reason, "too loud");
+//! assert_eq!(ban.nickname, None);
+//! assert_eq!(ban.to_params(), "banid=1 reason=too\\sloud");
+//! ```
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{Data, DeriveInput, Fields, GenericArgument, LitStr, PathArguments, Type};
+
+/// The field attributes of a single field.
+struct FieldOptions {
+    key: String,
+    escape: bool,
+    default: Option<syn::Path>,
+    skip: bool,
+}
+
+impl FieldOptions {
+    fn from_field(field: &syn::Field) -> syn::Result<FieldOptions> {
+        let ident = field.ident.as_ref().expect("named field");
+        let mut options = FieldOptions {
+            key: ident.to_string(),
+            escape: false,
+            default: None,
+            skip: false,
+        };
+        for attr in &field.attrs {
+            if !attr.path().is_ident("sqlib") {
+                continue;
+            }
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    let key: LitStr = meta.value()?.parse()?;
+                    options.key = key.value();
+                } else if meta.path.is_ident("escape") {
+                    options.escape = true;
+                } else if meta.path.is_ident("default") {
+                    let path: LitStr = meta.value()?.parse()?;
+                    options.default = Some(path.parse()?);
+                } else if meta.path.is_ident("skip") {
+                    options.skip = true;
+                } else {
+                    return Err(meta.error("unknown sqlib attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(options)
+    }
+}
+
+/// checks if the type is an `Option<T>`.
+fn is_option(ty: &Type) -> bool {
+    if let Type::Path(ref path) = *ty {
+        if let Some(segment) = path.path.segments.last() {
+            if segment.ident == "Option" {
+                if let PathArguments::AngleBracketed(ref args) = segment.arguments {
+                    return args.args.len() == 1
+                        && matches!(args.args[0], GenericArgument::Type(_));
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Derives `sqlib::map::FromStringMap` and `sqlib::map::ToParams`.
+#[proc_macro_derive(FromStringMap, attributes(sqlib))]
+pub fn derive_from_string_map(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    input,
+                    "FromStringMap can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "FromStringMap can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut defaults = Vec::new();
+    let mut updates = Vec::new();
+    let mut try_updates = Vec::new();
+    let mut params = Vec::new();
+
+    for field in fields {
+        let options = FieldOptions::from_field(field)?;
+        let ident = field.ident.as_ref().expect("named field");
+        let key = &options.key;
+
+        let default = match options.default {
+            Some(ref path) => quote! { #path() },
+            None => quote! { ::std::default::Default::default() },
+        };
+        defaults.push(quote! { #ident: #default });
+
+        if options.skip {
+            continue;
+        }
+
+        let value = if options.escape {
+            quote! { ::sqlib::escaping::unescape(value) }
+        } else {
+            quote! { value }
+        };
+        let param = if options.escape {
+            quote! { ::sqlib::escaping::escape(&value.to_string()) }
+        } else {
+            quote! { value.to_string() }
+        };
+
+        let invalid = quote! {
+            ::sqlib::error::Error::invalid_value(#key, value)
+        };
+
+        if is_option(&field.ty) {
+            updates.push(quote! {
+                if let Some(value) = map.get(#key) {
+                    if let Ok(value) = #value.parse() {
+                        self.#ident = Some(value);
+                    }
+                }
+            });
+            try_updates.push(quote! {
+                if let Some(value) = map.get(#key) {
+                    self.#ident = Some(#value.parse().map_err(|_| #invalid)?);
+                }
+            });
+            params.push(quote! {
+                if let Some(ref value) = self.#ident {
+                    params.push(format!("{}={}", #key, #param));
+                }
+            });
+        } else {
+            updates.push(quote! {
+                if let Some(value) = map.get(#key) {
+                    if let Ok(value) = #value.parse() {
+                        self.#ident = value;
+                    }
+                }
+            });
+            try_updates.push(quote! {
+                if let Some(value) = map.get(#key) {
+                    self.#ident = #value.parse().map_err(|_| #invalid)?;
+                }
+            });
+            params.push(quote! {
+                {
+                    let value = &self.#ident;
+                    params.push(format!("{}={}", #key, #param));
+                }
+            });
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::sqlib::map::FromStringMap for #name #ty_generics #where_clause {
+            fn from_map(map: &::sqlib::map::StringMap) -> Self {
+                let mut value = #name {
+                    #(#defaults,)*
+                };
+                ::sqlib::map::FromStringMap::mut_from_map(&mut value, map);
+                value
+            }
+
+            #[allow(unused_variables)]
+            fn mut_from_map(&mut self, map: &::sqlib::map::StringMap) {
+                #(#updates)*
+            }
+
+            fn try_from_map(map: &::sqlib::map::StringMap) -> ::sqlib::error::Result<Self> {
+                let mut value = #name {
+                    #(#defaults,)*
+                };
+                ::sqlib::map::FromStringMap::try_mut_from_map(&mut value, map)?;
+                Ok(value)
+            }
+
+            #[allow(unused_variables)]
+            fn try_mut_from_map(
+                &mut self,
+                map: &::sqlib::map::StringMap,
+            ) -> ::sqlib::error::Result<()> {
+                #(#try_updates)*
+                Ok(())
+            }
+        }
+
+        impl #impl_generics ::sqlib::map::ToParams for #name #ty_generics #where_clause {
+            fn to_params(&self) -> String {
+                let mut params: Vec<String> = Vec::new();
+                #(#params)*
+                params.join(" ")
+            }
+        }
+    })
+}
@@ -0,0 +1,93 @@
+//! The apikey module contains the ApiKey struct, a representation of an API key for the
+//! WebQuery interface.
+//!
+//! # Example
+//! ```
+//! use sqlib::apikey::{ApiKey, ApiKeyScope};
+//! use sqlib::response::FromResponse;
+//!
+//! let reply = "id=1 sid=1 cldbid=1 scope=manage time_left=unlimited created_at=1600000000 \
+//!              expires_at=0|id=2 sid=1 cldbid=5 scope=read time_left=86400 \
+//!              created_at=1600000000 expires_at=1600086400";
+//! let keys = Vec::<ApiKey>::from_response(reply).unwrap();
+//!
+//! assert_eq!(keys.len(), 2);
+//! assert_eq!(keys[1].scope, ApiKeyScope::Read);
+//! assert_eq!(keys[1].cldbid, 5);
+//! assert!(keys[1].apikey.is_none());
+//! ```
+
+use error;
+use error::Error;
+use map::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// ApiKeyScope contains the rights of an API key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum ApiKeyScope {
+    /// all commands
+    Manage,
+    /// all commands, that change the virtual server, but no instance commands
+    Write,
+    /// only commands, that read
+    #[default]
+    Read,
+}
+
+impl FromStr for ApiKeyScope {
+    type Err = error::Error;
+    fn from_str(s: &str) -> error::Result<Self> {
+        match s {
+            "manage" => Ok(ApiKeyScope::Manage),
+            "write" => Ok(ApiKeyScope::Write),
+            "read" => Ok(ApiKeyScope::Read),
+            _ => Err(Error::invalid_value("scope", s)),
+        }
+    }
+}
+
+impl fmt::Display for ApiKeyScope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let scope = match *self {
+            ApiKeyScope::Manage => "manage",
+            ApiKeyScope::Write => "write",
+            ApiKeyScope::Read => "read",
+        };
+        write!(f, "{}", scope)
+    }
+}
+
+/// ApiKey contains an API key of the WebQuery interface.
+#[derive(Debug, Clone, Default, PartialEq, Eq, FromStringMap)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ApiKey {
+    /// the key itself, it is only returned by `apikeyadd`
+    #[sqlib(escape)]
+    pub apikey: Option<String>,
+    /// id of the key
+    pub id: u64,
+    /// virtual server id
+    pub sid: u64,
+    /// database id of the client, that owns the key
+    pub cldbid: i64,
+    /// the commands, that the key may send
+    pub scope: ApiKeyScope,
+    /// the seconds until the key expires or `unlimited`
+    pub time_left: String,
+    /// unix timestamp
+    pub created_at: u64,
+    /// unix timestamp, 0 if the key does not expire
+    pub expires_at: u64,
+}
+
+impl FromStr for ApiKey {
+    type Err = error::Error;
+    fn from_str(s: &str) -> error::Result<Self> {
+        let map = to_map(s);
+        Ok(ApiKey::from_map(&map))
+    }
+}
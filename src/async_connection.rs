@@ -0,0 +1,440 @@
+//! The async_connection module contains the AsyncConnection struct, a Server Query connection
+//! for the tokio runtime. It needs the `async` feature.
+//!
+//! A background task owns the socket. Commands of all clones of an AsyncConnection are written
+//! in the order they are sent and the answers are matched to them in the same order, so many
+//! tasks can share one query session.
+//!
+//! # Example
+//! ```no_run
+//! extern crate sqlib;
+//! extern crate tokio;
+//!
+//! use sqlib::async_connection::AsyncConnection;
+//!
+//! # fn main() {
+//! let runtime = tokio::runtime::Builder::new_current_thread()
+//!     .enable_io()
+//!     .build()
+//!     .unwrap();
+//!
+//! let conn = runtime
+//!     .block_on(AsyncConnection::connect("127.0.0.1:10011"))
+//!     .unwrap();
+//!
+//! // both commands are written right away and answered in order
+//! let login = conn.login("serveradmin", "password");
+//! let select = conn.use_server_id(1);
+//! runtime.block_on(login).unwrap();
+//! runtime.block_on(select).unwrap();
+//!
+//! let clients = runtime.block_on(conn.clientlist()).unwrap();
+//! println!("{}", clients);
+//! # }
+//! ```
+
+use channel::ChannelList;
+use client::ClientList;
+use command::Command;
+use error::{Error, Result};
+use futures_core::Stream;
+use notification::Notification;
+use response::{decode_line, FromResponse, Line, ReplyCollector};
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot};
+
+// the number of lines the server sends after connecting
+const BANNER_LINES: usize = 2;
+
+enum Request {
+    Command(String, oneshot::Sender<Result<String>>),
+    Subscribe(mpsc::UnboundedSender<Notification>),
+}
+
+/// AsyncConnection provides an interface for a Server Query connection on the tokio runtime.
+///
+/// It is cheap to clone and all clones share the same query session. The session is closed
+/// when the last clone is dropped and all answers were received.
+#[derive(Clone)]
+pub struct AsyncConnection {
+    addr: SocketAddr,
+    requests: mpsc::UnboundedSender<Request>,
+}
+
+impl AsyncConnection {
+    /// connects to the given address and waits for the banner of the server.
+    ///
+    /// The returned future has to be polled inside of a tokio runtime, because the connection
+    /// is driven by a spawned task.
+    pub fn connect(addr: &str) -> Connect {
+        let state = match addr.parse::<SocketAddr>() {
+            Ok(addr) => ConnectState::Connecting(addr, Box::pin(TcpStream::connect(addr))),
+            Err(e) => ConnectState::Failed(Some(Error::from(e))),
+        };
+        Connect { state }
+    }
+
+    /// sends a given command to the server and returns the answer as a String, or the error.
+    pub fn send_command<C>(&self, command: &C) -> Reply<String>
+    where
+        C: Command,
+    {
+        self.query(command)
+    }
+
+    /// sends a given command to the server and parses the answer into the requested type.
+    pub fn query<T, C>(&self, command: &C) -> Reply<T>
+    where
+        T: FromResponse,
+        C: Command,
+    {
+        let command = command.string();
+        if command.is_empty() {
+            return Reply::failed(Error::from("no command"));
+        }
+        let (tx, rx) = oneshot::channel();
+        if self.requests.send(Request::Command(command, tx)).is_err() {
+            return Reply::failed(closed());
+        }
+        Reply {
+            rx: Some(rx),
+            error: None,
+            marker: PhantomData,
+        }
+    }
+
+    /// sends the quit command to the server.
+    pub fn quit(&self) -> Reply<()> {
+        self.query(&"quit")
+    }
+
+    /// sends the use command with the given id to the server.
+    pub fn use_server_id(&self, id: u64) -> Reply<()> {
+        self.query(&format!("use {}", id))
+    }
+
+    /// sends the login command with the name and password to the server.
+    pub fn login(&self, name: &str, pw: &str) -> Reply<()> {
+        self.query(&format!("login {} {}", name, pw))
+    }
+
+    /// sends the clientlist command to the server and parses the result.
+    pub fn clientlist(&self) -> Reply<ClientList> {
+        self.query(&"clientlist")
+    }
+
+    /// sends the channellist command to the server and parses the result.
+    pub fn channellist(&self) -> Reply<ChannelList> {
+        self.query(&"channellist")
+    }
+
+    /// registers for the notifications of an event, see `Connection::register_notifications`.
+    pub fn register_notifications(&self, event: &str, id: Option<i64>) -> Reply<()> {
+        match id {
+            Some(id) => self.query(&format!("servernotifyregister event={} id={}", event, id)),
+            None => self.query(&format!("servernotifyregister event={}", event)),
+        }
+    }
+
+    /// returns a new stream of all notifications, that are received from now on.
+    ///
+    /// Every call returns an independent stream. The stream ends when the connection is
+    /// closed.
+    pub fn notifications(&self) -> Notifications {
+        let (tx, rx) = mpsc::unbounded_channel();
+        // if the driver is gone, the sender is dropped and the stream ends right away
+        let _ = self.requests.send(Request::Subscribe(tx));
+        Notifications { rx }
+    }
+}
+
+impl fmt::Display for AsyncConnection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", &self.addr)
+    }
+}
+
+impl fmt::Debug for AsyncConnection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AsyncConnection {{ addr: {} }}", &self.addr)
+    }
+}
+
+fn closed() -> Error {
+    Error::from(io::Error::new(
+        io::ErrorKind::NotConnected,
+        "the connection is closed",
+    ))
+}
+
+enum ConnectState {
+    Failed(Option<Error>),
+    Connecting(
+        SocketAddr,
+        Pin<Box<dyn Future<Output = io::Result<TcpStream>> + Send>>,
+    ),
+    Banner(Option<AsyncConnection>, oneshot::Receiver<Result<()>>),
+}
+
+/// Connect is the future of `AsyncConnection::connect`.
+pub struct Connect {
+    state: ConnectState,
+}
+
+impl Future for Connect {
+    type Output = Result<AsyncConnection>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        loop {
+            let next = match self.state {
+                ConnectState::Failed(ref mut err) => {
+                    return Poll::Ready(Err(err.take().unwrap_or_else(closed)));
+                }
+                ConnectState::Connecting(addr, ref mut connecting) => {
+                    let stream = match connecting.as_mut().poll(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(Err(e)) => return Poll::Ready(Err(Error::from(e))),
+                        Poll::Ready(Ok(stream)) => stream,
+                    };
+                    let (requests, rx) = mpsc::unbounded_channel();
+                    let (ready, banner) = oneshot::channel();
+                    tokio::spawn(Driver::new(stream, rx, ready));
+                    let conn = AsyncConnection { addr, requests };
+                    ConnectState::Banner(Some(conn), banner)
+                }
+                ConnectState::Banner(ref mut conn, ref mut banner) => {
+                    return match Pin::new(banner).poll(cx) {
+                        Poll::Pending => Poll::Pending,
+                        Poll::Ready(Ok(Ok(()))) => Poll::Ready(conn.take().ok_or_else(closed)),
+                        Poll::Ready(Ok(Err(e))) => Poll::Ready(Err(e)),
+                        Poll::Ready(Err(_)) => Poll::Ready(Err(closed())),
+                    };
+                }
+            };
+            self.state = next;
+        }
+    }
+}
+
+/// Reply is the future of a command, that resolves to the parsed answer.
+pub struct Reply<T> {
+    rx: Option<oneshot::Receiver<Result<String>>>,
+    error: Option<Error>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Reply<T> {
+    fn failed(error: Error) -> Reply<T> {
+        Reply {
+            rx: None,
+            error: Some(error),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Future for Reply<T>
+where
+    T: FromResponse,
+{
+    type Output = Result<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        if let Some(error) = self.error.take() {
+            return Poll::Ready(Err(error));
+        }
+        let result = match self.rx {
+            Some(ref mut rx) => match Pin::new(rx).poll(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Ok(result)) => result,
+                Poll::Ready(Err(_)) => Err(closed()),
+            },
+            None => Err(closed()),
+        };
+        self.rx = None;
+        Poll::Ready(result.and_then(|s| T::from_response(&s)))
+    }
+}
+
+/// Notifications is a stream of the notifications of an AsyncConnection.
+pub struct Notifications {
+    rx: mpsc::UnboundedReceiver<Notification>,
+}
+
+impl Stream for Notifications {
+    type Item = Notification;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Notification>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+// Driver owns the socket. It writes the commands, reads the lines of the server and sends the
+// answers back in the order of the commands.
+struct Driver {
+    stream: TcpStream,
+    requests: mpsc::UnboundedReceiver<Request>,
+    requests_closed: bool,
+    ready: Option<oneshot::Sender<Result<()>>>,
+    banner_lines: usize,
+    pending: VecDeque<oneshot::Sender<Result<String>>>,
+    subscribers: Vec<mpsc::UnboundedSender<Notification>>,
+    write_buf: Vec<u8>,
+    read_buf: Vec<u8>,
+    collector: ReplyCollector,
+}
+
+impl Driver {
+    fn new(
+        stream: TcpStream,
+        requests: mpsc::UnboundedReceiver<Request>,
+        ready: oneshot::Sender<Result<()>>,
+    ) -> Driver {
+        Driver {
+            stream,
+            requests,
+            requests_closed: false,
+            ready: Some(ready),
+            banner_lines: BANNER_LINES,
+            pending: VecDeque::new(),
+            subscribers: Vec::new(),
+            write_buf: Vec::new(),
+            read_buf: Vec::new(),
+            collector: ReplyCollector::new(),
+        }
+    }
+
+    // accepts all new requests.
+    fn poll_requests(&mut self, cx: &mut Context) {
+        while !self.requests_closed {
+            match self.requests.poll_recv(cx) {
+                Poll::Ready(Some(Request::Command(command, tx))) => {
+                    self.write_buf.extend_from_slice(command.as_bytes());
+                    self.write_buf.push(b'\n');
+                    self.pending.push_back(tx);
+                }
+                Poll::Ready(Some(Request::Subscribe(tx))) => self.subscribers.push(tx),
+                Poll::Ready(None) => self.requests_closed = true,
+                Poll::Pending => break,
+            }
+        }
+    }
+
+    // writes as much of the buffered commands as possible.
+    fn poll_write(&mut self, cx: &mut Context) -> io::Result<()> {
+        while !self.write_buf.is_empty() {
+            match Pin::new(&mut self.stream).poll_write(cx, &self.write_buf) {
+                Poll::Ready(Ok(0)) => return Err(io::ErrorKind::WriteZero.into()),
+                Poll::Ready(Ok(n)) => {
+                    self.write_buf.drain(..n);
+                }
+                Poll::Ready(Err(e)) => return Err(e),
+                Poll::Pending => break,
+            }
+        }
+        Ok(())
+    }
+
+    // reads all available data and handles the complete lines.
+    fn poll_read(&mut self, cx: &mut Context) -> Result<()> {
+        let mut buf = [0u8; 4096];
+        loop {
+            let mut read_buf = ReadBuf::new(&mut buf);
+            match Pin::new(&mut self.stream).poll_read(cx, &mut read_buf) {
+                Poll::Ready(Ok(())) => {
+                    if read_buf.filled().is_empty() {
+                        return Err(Error::from(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "the connection was closed by the server",
+                        )));
+                    }
+                    self.read_buf.extend_from_slice(read_buf.filled());
+                    self.handle_lines()?;
+                }
+                Poll::Ready(Err(e)) => return Err(Error::from(e)),
+                Poll::Pending => return Ok(()),
+            }
+        }
+    }
+
+    fn handle_lines(&mut self) -> Result<()> {
+        while let Some(pos) = self.read_buf.iter().position(|&b| b == b'\n') {
+            let bytes: Vec<u8> = self.read_buf.drain(..=pos).collect();
+            self.handle_line(&decode_line(&bytes))?;
+        }
+        Ok(())
+    }
+
+    fn handle_line(&mut self, line: &str) -> Result<()> {
+        if self.banner_lines > 0 {
+            self.banner_lines -= 1;
+            if self.banner_lines == BANNER_LINES - 1 && line.trim() != "TS3" {
+                return Err(Error::from("the given server is not a TS3 server"));
+            }
+            if self.banner_lines == 0 {
+                if let Some(ready) = self.ready.take() {
+                    let _ = ready.send(Ok(()));
+                }
+            }
+            return Ok(());
+        }
+
+        match self.collector.push_line(line) {
+            Line::Notification(notification) => {
+                self.subscribers
+                    .retain(|tx| tx.send(notification.clone()).is_ok());
+            }
+            Line::Reply(answer) => {
+                // the caller may have dropped its Reply, then the answer is not needed anymore
+                if let Some(tx) = self.pending.pop_front() {
+                    let _ = tx.send(answer);
+                }
+            }
+            Line::Partial => {}
+        }
+        Ok(())
+    }
+
+    // sends the error to everyone, who is waiting. Everyone gets a clone of the error, so its
+    // kind is kept.
+    fn fail(&mut self, error: Error) {
+        for tx in self.pending.drain(..) {
+            let _ = tx.send(Err(error.clone()));
+        }
+        if let Some(ready) = self.ready.take() {
+            let _ = ready.send(Err(error));
+        }
+        self.subscribers.clear();
+    }
+}
+
+impl Future for Driver {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let this = &mut *self;
+        this.poll_requests(cx);
+        if let Err(e) = this.poll_write(cx) {
+            this.fail(Error::from(e));
+            return Poll::Ready(());
+        }
+        if let Err(e) = this.poll_read(cx) {
+            this.fail(e);
+            return Poll::Ready(());
+        }
+        if this.requests_closed && this.pending.is_empty() && this.write_buf.is_empty() {
+            return Poll::Ready(());
+        }
+        Poll::Pending
+    }
+}
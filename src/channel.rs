@@ -6,6 +6,7 @@ use client::{Client, ClientList};
 use error;
 use escaping::*;
 use map::*;
+use response::FromResponse;
 use rustc_serialize::json;
 use std::cmp;
 use std::fmt;
@@ -22,13 +23,22 @@ use std::str::FromStr;
 /// assert!(channel.is_empty());
 /// assert_eq!("test".to_string(), format!("{}", channel));
 /// ```
-#[derive(Debug, Clone, RustcDecodable, RustcEncodable)]
+#[derive(Debug, Clone, RustcDecodable, RustcEncodable, FromStringMap)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Channel {
     /// channel id
     pub cid: i64,
+    /// channel id of the parent channel, 0 for a top level channel
+    pub pid: i64,
+    /// channel id of the channel above this one, 0 for the first channel
+    pub channel_order: i64,
     /// channel name
+    #[sqlib(escape)]
     pub channel_name: String,
     /// A vector of clients, who are in the channel.
+    #[sqlib(skip)]
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
     pub clients: Vec<Client>,
 }
 
@@ -36,6 +46,8 @@ impl Default for Channel {
     fn default() -> Channel {
         Channel {
             cid: 0,
+            pid: 0,
+            channel_order: 0,
             channel_name: String::new(),
             clients: Vec::new(),
         }
@@ -58,9 +70,7 @@ impl Channel {
 
     /// Create a new Channel from a given map.
     pub fn from_map(map: &StringMap) -> Channel {
-        let mut channel = Channel::default();
-        channel.mut_from_map(map);
-        channel
+        <Channel as FromStringMap>::from_map(map)
     }
 
     /// Create a new Channel from a given Channel and a map.
@@ -72,9 +82,7 @@ impl Channel {
 
     /// Mutate self from a given map.
     pub fn mut_from_map(&mut self, map: &StringMap) {
-        update_from_map(map, "cid", &mut self.cid);
-        update_from_map(map, "channel_name", &mut self.channel_name);
-        self.unescape();
+        FromStringMap::mut_from_map(self, map);
     }
 
     pub fn is_empty(&self) -> bool {
@@ -105,8 +113,8 @@ impl Channel {
     }
 
     /// Creates a JSON String from self.
-    pub fn as_json(&self) -> String {
-        json::encode(self).unwrap_or_default()
+    pub fn as_json(&self) -> error::Result<String> {
+        Ok(json::encode(self)?)
     }
 }
 
@@ -168,6 +176,7 @@ impl Ord for Channel {
 /// assert_eq!(channels, channels2);
 /// ```
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Clone, RustcDecodable, RustcEncodable)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ChannelList(Vec<Channel>);
 
 impl Default for ChannelList {
@@ -208,9 +217,71 @@ impl ChannelList {
         }
     }
 
+    /// returns the channel with the given channel id.
+    pub fn channel(&self, cid: i64) -> Option<&Channel> {
+        self.iter().find(|channel| channel.cid == cid)
+    }
+
+    /// returns the channel with the given channel id as mutable reference.
+    pub fn channel_mut(&mut self, cid: i64) -> Option<&mut Channel> {
+        self.as_mut().iter_mut().find(|channel| channel.cid == cid)
+    }
+
+    /// removes the client with the given client id from its channel and returns it.
+    pub fn remove_client(&mut self, clid: i64) -> Option<Client> {
+        for channel in self.as_mut().iter_mut() {
+            if let Some(pos) = channel.clients.iter().position(|c| c.clid == clid) {
+                return Some(channel.clients.remove(pos));
+            }
+        }
+        None
+    }
+
+    /// sorts the channels in the order of the channel tree, like the channellist command does:
+    /// every channel is followed by its sub channels and the siblings are sorted by their
+    /// channel_order.
+    ///
+    /// # Example
+    /// ```
+    /// use sqlib::channel::{Channel, ChannelList};
+    ///
+    /// let lobby = Channel::new(1, "lobby".to_string());
+    /// let mut games = Channel::new(2, "games".to_string());
+    /// let mut sub = Channel::new(3, "sub".to_string());
+    /// games.channel_order = 1;
+    /// sub.pid = 1;
+    ///
+    /// let mut channels = ChannelList::from(vec![games, sub, lobby]);
+    /// channels.sort_tree();
+    ///
+    /// let cids: Vec<_> = channels.iter().map(|c| c.cid).collect();
+    /// assert_eq!(cids, vec![1, 3, 2]);
+    /// ```
+    pub fn sort_tree(&mut self) {
+        let mut channels: Vec<_> = self.as_mut().drain(..).collect();
+        let mut sorted = Vec::with_capacity(channels.len());
+        ChannelList::append_sub_channels(0, &mut channels, &mut sorted);
+        // channels with a broken parent or order are kept at the end
+        sorted.extend(channels);
+        *self.as_mut() = sorted;
+    }
+
+    fn append_sub_channels(pid: i64, channels: &mut Vec<Channel>, sorted: &mut Vec<Channel>) {
+        let mut order = 0;
+        while let Some(pos) = channels
+            .iter()
+            .position(|c| c.pid == pid && c.channel_order == order)
+        {
+            let channel = channels.remove(pos);
+            order = channel.cid;
+            sorted.push(channel);
+            ChannelList::append_sub_channels(order, channels, sorted);
+        }
+    }
+
     /// creates a JSON String from a ChannelList
-    pub fn as_json(&self) -> String {
-        json::encode(self.as_ref()).unwrap_or_default()
+    pub fn as_json(&self) -> error::Result<String> {
+        Ok(json::encode(self.as_ref())?)
     }
 }
 
@@ -235,11 +306,22 @@ impl AsMut<Vec<Channel>> for ChannelList {
     }
 }
 
+impl FromResponse for ChannelList {
+    fn from_response(response: &str) -> error::Result<Self> {
+        let items = Vec::<Channel>::from_response(response)?;
+        Ok(ChannelList(items))
+    }
+
+    fn from_response_strict(response: &str) -> error::Result<Self> {
+        let items = Vec::<Channel>::from_response_strict(response)?;
+        Ok(ChannelList(items))
+    }
+}
+
 impl FromStr for ChannelList {
     type Err = error::Error;
     fn from_str(s: &str) -> error::Result<Self> {
-        let maps: Vec<_> = s.split('|').map(to_map).collect();
-        Ok(ChannelList::from_maps(&maps))
+        ChannelList::from_response(s)
     }
 }
 
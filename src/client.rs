@@ -5,6 +5,7 @@
 use error;
 use escaping::*;
 use map::*;
+use response::FromResponse;
 use rustc_serialize::json;
 use std::cmp;
 use std::fmt;
@@ -21,8 +22,7 @@ use std::str::FromStr;
 ///     cid: 1,
 ///     client_database_id: 1,
 ///     client_nickname: "John Doe".to_string(),
-///     client_type: 0,
-///     connection_connected_time: 0
+///     ..client::Client::default()
 /// };
 ///
 /// assert!(client.is_client());
@@ -31,7 +31,9 @@ use std::str::FromStr;
 ///
 /// assert_eq!("John Doe (0)".to_string(), client_print);
 /// ```
-#[derive(Debug, Clone, RustcDecodable, RustcEncodable)]
+#[derive(Debug, Clone, Default, RustcDecodable, RustcEncodable, FromStringMap)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Client {
     /// client id
     pub clid: i64,
@@ -40,24 +42,67 @@ pub struct Client {
     /// client database id
     pub client_database_id: i64,
     /// client nickname
+    #[sqlib(escape)]
     pub client_nickname: String,
     /// client type: 0 is client and 1 is query client
     pub client_type: i64,
-    /// connection time in milliseconds
+    /// connection time in milliseconds, only `clientinfo` returns it
     pub connection_connected_time: i64,
-}
-
-impl Default for Client {
-    fn default() -> Client {
-        Client {
-            clid: 0,
-            cid: 0,
-            client_database_id: 0,
-            client_nickname: String::new(),
-            client_type: 0,
-            connection_connected_time: 0,
-        }
-    }
+    /// unique identifier (`-uid`)
+    #[sqlib(escape)]
+    pub client_unique_identifier: String,
+    /// 1 if the client is away (`-away`)
+    pub client_away: i64,
+    /// away message (`-away`)
+    #[sqlib(escape)]
+    pub client_away_message: String,
+    /// 1 if the client is talking (`-voice`)
+    pub client_flag_talking: i64,
+    /// 1 if the microphone is muted (`-voice`)
+    pub client_input_muted: i64,
+    /// 1 if the speakers are muted (`-voice`)
+    pub client_output_muted: i64,
+    /// 0 if the microphone is disabled (`-voice`)
+    pub client_input_hardware: i64,
+    /// 0 if the speakers are disabled (`-voice`)
+    pub client_output_hardware: i64,
+    /// talk power (`-voice`)
+    pub client_talk_power: i64,
+    /// 1 if the client may talk in a moderated channel (`-voice`)
+    pub client_is_talker: i64,
+    /// 1 if the client is a priority speaker (`-voice`)
+    pub client_is_priority_speaker: i64,
+    /// 1 if the client is recording (`-voice`)
+    pub client_is_recording: i64,
+    /// 1 if the client is a channel commander (`-voice`)
+    pub client_is_channel_commander: i64,
+    /// idle time in milliseconds (`-times`)
+    pub client_idle_time: i64,
+    /// unix time of the first connection (`-times`)
+    pub client_created: i64,
+    /// unix time of the last connection (`-times`)
+    pub client_lastconnected: i64,
+    /// comma seperated server group ids (`-groups`), see `servergroups`
+    pub client_servergroups: String,
+    /// channel group id (`-groups`)
+    pub client_channel_group_id: i64,
+    /// channel id, that the channel group is inherited from (`-groups`)
+    pub client_channel_group_inherited_channel_id: i64,
+    /// client version (`-info`)
+    #[sqlib(escape)]
+    pub client_version: String,
+    /// operating system of the client (`-info`)
+    #[sqlib(escape)]
+    pub client_platform: String,
+    /// icon id (`-icon`)
+    pub client_icon_id: i64,
+    /// country code (`-country`)
+    pub client_country: String,
+    /// IP address (`-ip`)
+    pub connection_client_ip: String,
+    /// badges (`-badges`)
+    #[sqlib(escape)]
+    pub client_badges: String,
 }
 
 impl Client {
@@ -74,15 +119,35 @@ impl Client {
         self.client_type == 0
     }
 
+    /// checks if the client is away. It needs the `-away` option.
+    pub fn is_away(&self) -> bool {
+        self.client_away == 1
+    }
+
+    /// returns the ids of the server groups. It needs the `-groups` option.
+    ///
+    /// # Example
+    /// ```
+    /// use sqlib::client::Client;
+    ///
+    /// let client: Client = "clid=1 client_servergroups=6,8".parse().unwrap();
+    ///
+    /// assert_eq!(client.servergroups(), vec![6, 8]);
+    /// ```
+    pub fn servergroups(&self) -> Vec<i64> {
+        self.client_servergroups
+            .split(',')
+            .filter_map(|id| id.parse().ok())
+            .collect()
+    }
+
     fn unescape(&mut self) {
         self.client_nickname = unescape(&self.client_nickname);
     }
 
     /// creates a Client from a given map.
     pub fn from_map(map: &StringMap) -> Client {
-        let mut client = Client::default();
-        client.mut_from_map(map);
-        client
+        <Client as FromStringMap>::from_map(map)
     }
 
     /// updates a given Client from a given map.
@@ -94,17 +159,7 @@ impl Client {
 
     /// mutates self from a given map.
     pub fn mut_from_map(&mut self, map: &StringMap) {
-        update_from_map(map, "clid", &mut self.clid);
-        update_from_map(map, "cid", &mut self.cid);
-        update_from_map(map, "client_database_id", &mut self.client_database_id);
-        update_from_map(map, "client_nickname", &mut self.client_nickname);
-        update_from_map(map, "client_type", &mut self.client_type);
-        update_from_map(
-            map,
-            "connection_connected_time",
-            &mut self.connection_connected_time,
-        );
-        self.unescape();
+        FromStringMap::mut_from_map(self, map);
     }
 
     fn connection_connected_time_string(&self) -> String {
@@ -123,8 +178,8 @@ impl Client {
     }
 
     /// creates a JSON String from self.
-    pub fn as_json(&self) -> String {
-        json::encode(self).unwrap_or_default()
+    pub fn as_json(&self) -> error::Result<String> {
+        Ok(json::encode(self)?)
     }
 }
 
@@ -185,6 +240,7 @@ impl Ord for Client {
 /// assert_eq!(clients, clients2);
 /// ```
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Clone, Default, RustcDecodable, RustcEncodable)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ClientList(Vec<Client>);
 
 impl ClientList {
@@ -217,16 +273,27 @@ impl ClientList {
     }
 
     /// creats a JSON String from a ClientList
-    pub fn as_json(&self) -> String {
-        json::encode(self).unwrap_or_default()
+    pub fn as_json(&self) -> error::Result<String> {
+        Ok(json::encode(self)?)
+    }
+}
+
+impl FromResponse for ClientList {
+    fn from_response(response: &str) -> error::Result<Self> {
+        let items = Vec::<Client>::from_response(response)?;
+        Ok(ClientList(items))
+    }
+
+    fn from_response_strict(response: &str) -> error::Result<Self> {
+        let items = Vec::<Client>::from_response_strict(response)?;
+        Ok(ClientList(items))
     }
 }
 
 impl FromStr for ClientList {
     type Err = error::Error;
     fn from_str(s: &str) -> error::Result<Self> {
-        let maps: Vec<_> = s.split('|').map(to_map).collect();
-        Ok(ClientList::from_maps(&maps))
+        ClientList::from_response(s)
     }
 }
 
@@ -276,3 +343,143 @@ impl fmt::Display for ClientList {
         write!(f, " ]")
     }
 }
+
+/// ClientListOptions contains the options of a `clientlist` command, that add fields to the
+/// Clients.
+///
+/// # Example
+/// ```
+/// use sqlib::client::ClientListOptions;
+///
+/// let options = ClientListOptions::new().uid().away().groups();
+///
+/// assert_eq!(options.command(), "clientlist -uid -away -groups");
+/// assert_eq!(ClientListOptions::new().command(), "clientlist");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClientListOptions {
+    uid: bool,
+    away: bool,
+    voice: bool,
+    times: bool,
+    groups: bool,
+    info: bool,
+    icon: bool,
+    country: bool,
+    ip: bool,
+    badges: bool,
+}
+
+impl ClientListOptions {
+    /// creates ClientListOptions without any option.
+    pub fn new() -> ClientListOptions {
+        ClientListOptions::default()
+    }
+
+    /// creates ClientListOptions with all options.
+    pub fn all() -> ClientListOptions {
+        ClientListOptions::new()
+            .uid()
+            .away()
+            .voice()
+            .times()
+            .groups()
+            .info()
+            .icon()
+            .country()
+            .ip()
+            .badges()
+    }
+
+    /// adds the unique identifier.
+    pub fn uid(mut self) -> ClientListOptions {
+        self.uid = true;
+        self
+    }
+
+    /// adds the away status and message.
+    pub fn away(mut self) -> ClientListOptions {
+        self.away = true;
+        self
+    }
+
+    /// adds the talking, muted and talk power fields.
+    pub fn voice(mut self) -> ClientListOptions {
+        self.voice = true;
+        self
+    }
+
+    /// adds the idle time and the times of the first and last connection.
+    pub fn times(mut self) -> ClientListOptions {
+        self.times = true;
+        self
+    }
+
+    /// adds the server and channel groups.
+    pub fn groups(mut self) -> ClientListOptions {
+        self.groups = true;
+        self
+    }
+
+    /// adds the version and the platform.
+    pub fn info(mut self) -> ClientListOptions {
+        self.info = true;
+        self
+    }
+
+    /// adds the icon id.
+    pub fn icon(mut self) -> ClientListOptions {
+        self.icon = true;
+        self
+    }
+
+    /// adds the country code.
+    pub fn country(mut self) -> ClientListOptions {
+        self.country = true;
+        self
+    }
+
+    /// adds the IP address.
+    pub fn ip(mut self) -> ClientListOptions {
+        self.ip = true;
+        self
+    }
+
+    /// adds the badges.
+    pub fn badges(mut self) -> ClientListOptions {
+        self.badges = true;
+        self
+    }
+
+    /// creates the options, e.g. `-uid -away`.
+    pub fn params(&self) -> String {
+        let options = [
+            (self.uid, "-uid"),
+            (self.away, "-away"),
+            (self.voice, "-voice"),
+            (self.times, "-times"),
+            (self.groups, "-groups"),
+            (self.info, "-info"),
+            (self.icon, "-icon"),
+            (self.country, "-country"),
+            (self.ip, "-ip"),
+            (self.badges, "-badges"),
+        ];
+        options
+            .iter()
+            .filter(|option| option.0)
+            .map(|option| option.1)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// creates the `clientlist` command with the options.
+    pub fn command(&self) -> String {
+        let params = self.params();
+        if params.is_empty() {
+            "clientlist".to_string()
+        } else {
+            format!("clientlist {}", params)
+        }
+    }
+}
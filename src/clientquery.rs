@@ -0,0 +1,232 @@
+//! The clientquery module contains the ClientQueryConnection struct, a connection to the
+//! ClientQuery plugin of a TeamSpeak client (port 25639 by default).
+//!
+//! The ClientQuery uses the same line protocol, escaping and errors as the Server Query, so the
+//! same parsing is used. The commands work on a server connection handler of the client, that is
+//! selected with `use_schandler_id`.
+//!
+//! # Example
+//! ```no_run
+//! use sqlib::clientquery::ClientQueryConnection;
+//!
+//! let mut conn = ClientQueryConnection::new("127.0.0.1:25639").unwrap();
+//! conn.auth("ABCD-EFGH-IJKL-MNOP-QRST-UVWX").unwrap();
+//! conn.register_notifications("notifytextmessage", None).unwrap();
+//!
+//! loop {
+//!     let notification = conn.wait_notification().unwrap();
+//!     println!("{}", notification);
+//! }
+//! ```
+
+use channel::ChannelList;
+use client::ClientList;
+use command::Command;
+use connection::Connection;
+use error;
+use error::Error;
+use escaping::escape;
+use map::*;
+use notification::Notification;
+use ratelimit::RateLimit;
+use response::FromResponse;
+use std::fmt;
+use std::net;
+use std::time::Duration;
+use textmessage::TextMessageTarget;
+use transport::Transport;
+
+/// The first line of the banner of the ClientQuery.
+pub const CLIENT_QUERY_BANNER: &str = "TS3 Client";
+
+/// ClientQueryConnection provides an interface for a ClientQuery connection.
+#[derive(Debug)]
+pub struct ClientQueryConnection {
+    conn: Connection,
+    schandlerid: u64,
+}
+
+impl ClientQueryConnection {
+    /// creates a new ClientQueryConnection from an adress given as a string reference.
+    pub fn new(addr: &str) -> error::Result<ClientQueryConnection> {
+        let a: net::SocketAddrV4 = addr.parse()?;
+        let c = net::TcpStream::connect(a)?;
+        ClientQueryConnection::with_transport(&a.to_string(), c)
+    }
+
+    /// creates a new ClientQueryConnection over an already connected Transport and checks the
+    /// banner of the client.
+    ///
+    /// # Example
+    /// ```
+    /// use sqlib::clientquery::ClientQueryConnection;
+    /// use std::io::{BufRead, BufReader, Write};
+    /// use std::net::{TcpListener, TcpStream};
+    /// use std::thread;
+    ///
+    /// // a stand-in for the ClientQuery plugin
+    /// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    /// let addr = listener.local_addr().unwrap();
+    /// thread::spawn(move || {
+    ///     let (mut stream, _) = listener.accept().unwrap();
+    ///     write!(stream, "TS3 Client\n\rWelcome to the TeamSpeak 3 ClientQuery interface\n\r").unwrap();
+    ///     write!(stream, "selected schandlerid=1\n\r").unwrap();
+    ///     for line in BufReader::new(stream.try_clone().unwrap()).lines() {
+    ///         if line.unwrap() == "whoami" {
+    ///             write!(stream, "clid=5 cid=2\n\r").unwrap();
+    ///         }
+    ///         write!(stream, "error id=0 msg=ok\n\r").unwrap();
+    ///     }
+    /// });
+    ///
+    /// let stream = TcpStream::connect(addr).unwrap();
+    /// let mut conn = ClientQueryConnection::with_transport(&addr.to_string(), stream).unwrap();
+    /// assert_eq!(conn.schandler_id(), 1);
+    ///
+    /// conn.auth("ABCD-EFGH").unwrap();
+    /// assert_eq!(conn.whoami().unwrap()["clid"], "5");
+    /// ```
+    pub fn with_transport<T>(addr: &str, transport: T) -> error::Result<ClientQueryConnection>
+    where
+        T: Transport + 'static,
+    {
+        let mut conn = Connection::without_banner(addr, transport);
+        // the client does not ban for flooding
+        conn.set_rate_limit(RateLimit::disabled());
+
+        if conn.read_line()?.trim() != CLIENT_QUERY_BANNER {
+            return Err(Error::from("the given client is not a TS3 ClientQuery"));
+        }
+        // the welcome lines differ between the client versions, the banner ends with the
+        // selected server connection handler
+        let schandlerid = loop {
+            let line = conn.read_line()?;
+            if let Some(id) = line.trim().strip_prefix("selected ") {
+                let map = to_map(id);
+                let mut schandlerid = 0;
+                update_from_map(&map, "schandlerid", &mut schandlerid);
+                break schandlerid;
+            }
+        };
+
+        Ok(ClientQueryConnection { conn, schandlerid })
+    }
+
+    /// authenticates with the API key of the ClientQuery plugin.
+    pub fn auth(&mut self, api_key: &str) -> error::Result<()> {
+        self.send_command(&format!("auth apikey={}", escape(api_key)))
+            .map(|_| ())
+    }
+
+    /// sends a given command to the client and returns the answer as a String, or the error.
+    pub fn send_command<C>(&mut self, command: &C) -> error::Result<String>
+    where
+        C: Command,
+    {
+        self.conn.send_command(command)
+    }
+
+    /// sends a given command to the client and parses the answer into the requested type.
+    pub fn query<T, C>(&mut self, command: &C) -> error::Result<T>
+    where
+        T: FromResponse,
+        C: Command,
+    {
+        self.conn.query(command)
+    }
+
+    /// returns the id of the server connection handler, that was selected last.
+    pub fn schandler_id(&self) -> u64 {
+        self.schandlerid
+    }
+
+    /// asks the client for the id of the server connection handler, that is selected in its
+    /// user interface.
+    pub fn current_schandler_id(&mut self) -> error::Result<u64> {
+        let map = self.conn.send_command_to_map(&"currentschandlerid")?;
+        let mut schandlerid = 0;
+        update_from_map(&map, "schandlerid", &mut schandlerid);
+        Ok(schandlerid)
+    }
+
+    /// selects the server connection handler with the given id for the following commands.
+    pub fn use_schandler_id(&mut self, id: u64) -> error::Result<()> {
+        self.send_command(&format!("use schandlerid={}", id))?;
+        self.schandlerid = id;
+        Ok(())
+    }
+
+    /// registers for the notifications of an event, e.g. `notifytextmessage` or `any`, of the
+    /// server connection handler with the given id. `None` means all handlers.
+    pub fn register_notifications(
+        &mut self,
+        event: &str,
+        schandlerid: Option<u64>,
+    ) -> error::Result<()> {
+        let cmd = format!(
+            "clientnotifyregister schandlerid={} event={}",
+            schandlerid.unwrap_or(0),
+            event
+        );
+        self.send_command(&cmd).map(|_| ())
+    }
+
+    /// unregisters from all notifications.
+    pub fn unregister_notifications(&mut self) -> error::Result<()> {
+        self.send_command(&"clientnotifyunregister").map(|_| ())
+    }
+
+    /// returns all notifications, that were received while waiting for the answers of commands.
+    pub fn take_notifications(&mut self) -> Vec<Notification> {
+        self.conn.take_notifications()
+    }
+
+    /// returns the next notification and waits for it, if none was received yet.
+    pub fn wait_notification(&mut self) -> error::Result<Notification> {
+        self.conn.wait_notification()
+    }
+
+    /// like `wait_notification`, but it returns `Ok(None)`, if no notification was received
+    /// within the timeout.
+    pub fn wait_notification_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> error::Result<Option<Notification>> {
+        self.conn.wait_notification_timeout(timeout)
+    }
+
+    /// returns the client id and channel id of the own client on the selected server.
+    pub fn whoami(&mut self) -> error::Result<StringMap> {
+        self.query(&"whoami")
+    }
+
+    /// sends a text message to a client, the own channel or the server.
+    pub fn send_text_message(
+        &mut self,
+        target: &TextMessageTarget,
+        msg: &str,
+    ) -> error::Result<()> {
+        self.send_command(&target.command(msg)).map(|_| ())
+    }
+
+    /// sends the clientlist command to the client and parses the result.
+    pub fn clientlist(&mut self) -> error::Result<ClientList> {
+        self.query(&"clientlist")
+    }
+
+    /// sends the channellist command to the client and parses the result.
+    pub fn channellist(&mut self) -> error::Result<ChannelList> {
+        self.query(&"channellist")
+    }
+
+    /// sends the quit command to the client and shuts the connection down.
+    pub fn quit(&mut self) -> error::Result<()> {
+        self.conn.quit()
+    }
+}
+
+impl fmt::Display for ClientQueryConnection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", &self.conn)
+    }
+}
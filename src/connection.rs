@@ -1,55 +1,197 @@
 //! The connection module contains the Connection struct, that provides an interface for a Server
 //! Query connection.
 
+use apikey::{ApiKey, ApiKeyScope};
 use channel::ChannelList;
-use client::ClientList;
+use client::{ClientList, ClientListOptions};
 use command::Command;
 use error;
-use error::{Error, SQError};
+use error::{Error, DATABASE_EMPTY_RESULT_SET};
+use escaping::{escape, unescape};
 use map::*;
+use metrics::Metrics;
+use notification::Notification;
+use querylogin::{QueryLogin, QueryLoginFilter};
+use ratelimit::RateLimit;
+use response::{decode_line, FromResponse, Line, ReplyCollector};
+use server::{CreatedServer, ServerProperties};
+#[cfg(feature = "ssh")]
+use ssh::SshTransport;
+use std::collections::VecDeque;
 use std::fmt;
+use std::io;
 use std::io::prelude::*;
 use std::io::BufReader;
 use std::net;
 use std::string::String;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use textmessage::TextMessageTarget;
+#[cfg(feature = "tls")]
+use tls::{TlsConfig, TlsTransport};
+use trace;
+use transport::Transport;
+
+/// The number of notifications, that a Connection queues by default, before it drops the
+/// oldest ones.
+pub const DEFAULT_NOTIFICATION_CAPACITY: usize = 1024;
+
+/// The number of commands, that `send_batch` sends ahead of their answers.
+pub const PIPELINE_DEPTH: usize = 32;
 
 /// Connection provides an interface for a Server Query connection.
 #[derive(Debug)]
 pub struct Connection {
-    addr: net::SocketAddrV4,
-    conn: BufReader<net::TcpStream>,
+    addr: String,
+    conn: BufReader<Box<dyn Transport>>,
+    // a line, that was interrupted by a read timeout
+    partial_line: Vec<u8>,
+    notifications: VecDeque<Notification>,
+    notification_capacity: usize,
+    dropped_notifications: u64,
+    rate_limit: RateLimit,
+    metrics: Option<Arc<Metrics>>,
 }
 
 impl Connection {
     /// creates a new Connection from an adress given as a string reference.
     pub fn new(addr: &str) -> error::Result<Connection> {
-        let addr = addr.to_string();
-        let a = addr.parse()?;
+        let a: net::SocketAddrV4 = addr.parse()?;
         let c = net::TcpStream::connect(a)?;
-        let mut connection = Connection {
-            addr: a,
-            conn: BufReader::new(c),
-        };
-        let mut tmp = String::new();
-        connection.conn.read_line(&mut tmp)?;
+        Connection::with_transport(&a.to_string(), c)
+    }
+
+    /// creates a new Connection over the SSH query port and authenticates with the Server
+    /// Query login. It needs the `ssh` feature.
+    ///
+    /// The SHA256 fingerprint of the host key has to match `fingerprint`, otherwise the
+    /// password is not sent (see `SshTransport::connect_pinned` for the formats).
+    #[cfg(feature = "ssh")]
+    pub fn new_ssh(
+        addr: &str,
+        login: &str,
+        password: &str,
+        fingerprint: &str,
+    ) -> error::Result<Connection> {
+        let transport = SshTransport::connect_pinned(addr, login, password, fingerprint)?;
+        Connection::with_transport(addr, transport)
+    }
+
+    /// like `new_ssh`, but the host key is not checked. Anybody, who can intercept the
+    /// connection, gets the password, so only use it in a trusted network.
+    #[cfg(feature = "ssh")]
+    pub fn new_ssh_unverified(
+        addr: &str,
+        login: &str,
+        password: &str,
+    ) -> error::Result<Connection> {
+        let transport = SshTransport::connect_unverified(addr, login, password)?;
+        Connection::with_transport(addr, transport)
+    }
+
+    /// creates a new Connection over TLS. It needs the `tls` feature.
+    ///
+    /// The banner of the server is checked like in `new`.
+    #[cfg(feature = "tls")]
+    pub fn new_tls(addr: &str, config: &TlsConfig) -> error::Result<Connection> {
+        let transport = TlsTransport::connect(addr, config)?;
+        Connection::with_transport(addr, transport)
+    }
+
+    /// creates a new Connection over an already connected Transport and checks the banner of
+    /// the server. The address is only used for displaying the Connection.
+    pub fn with_transport<T>(addr: &str, transport: T) -> error::Result<Connection>
+    where
+        T: Transport + 'static,
+    {
+        let _span = trace::connect_span(addr);
+        let mut connection = Connection::without_banner(addr, transport);
+        let result = connection.read_banner().map(|_| connection);
+        trace::connected(&result);
+        result
+    }
+
+    // reads the two lines of the banner of the server.
+    fn read_banner(&mut self) -> error::Result<()> {
+        let tmp = self.read_line()?;
         if tmp.trim() != "TS3" {
             return Err(From::from("the given server is not a TS3 server"));
         }
-        connection.read_line(&mut tmp)?;
-        Ok(connection)
+        self.read_line()?;
+        Ok(())
+    }
+
+    // creates a Connection, that has not read the banner yet.
+    pub(crate) fn without_banner<T>(addr: &str, transport: T) -> Connection
+    where
+        T: Transport + 'static,
+    {
+        Connection {
+            addr: addr.to_string(),
+            conn: BufReader::new(Box::new(transport)),
+            partial_line: Vec::new(),
+            notifications: VecDeque::new(),
+            notification_capacity: DEFAULT_NOTIFICATION_CAPACITY,
+            dropped_notifications: 0,
+            rate_limit: RateLimit::default(),
+            metrics: None,
+        }
     }
 
-    fn read_line<'a>(&mut self, buf: &'a mut String) -> error::Result<&'a str> {
-        let _ = self.conn.read_line(buf)?;
-        Ok(buf.trim_left_matches(char::is_control))
+    // reads the next line.
+    pub(crate) fn read_line(&mut self) -> error::Result<String> {
+        let n = self.conn.read_until(b'\n', &mut self.partial_line)?;
+        if n == 0 && self.partial_line.is_empty() {
+            return Err(Error::from(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the connection was closed by the server",
+            )));
+        }
+        let line = decode_line(&self.partial_line);
+        self.partial_line.clear();
+        Ok(line)
     }
 
-    fn get_stream_mut(&mut self) -> &mut net::TcpStream {
+    fn get_stream_mut(&mut self) -> &mut Box<dyn Transport> {
         self.conn.get_mut()
     }
 
+    /// replaces the RateLimit of the Connection. The default are the default flood limits of
+    /// the server.
+    pub fn set_rate_limit(&mut self, rate_limit: RateLimit) {
+        self.rate_limit = rate_limit;
+    }
+
+    /// returns the RateLimit of the Connection.
+    pub fn rate_limit(&self) -> &RateLimit {
+        &self.rate_limit
+    }
+
+    /// sets the Metrics, that count the commands, errors, notifications and flood backoffs of
+    /// the Connection. A Metrics can be shared by many connections.
+    pub fn set_metrics(&mut self, metrics: Arc<Metrics>) {
+        self.metrics = Some(metrics);
+    }
+
+    /// returns the Metrics of the Connection, if they were set.
+    pub fn metrics(&self) -> Option<&Arc<Metrics>> {
+        self.metrics.as_ref()
+    }
+
+    // updates the Metrics, if they were set.
+    fn observe<F>(&self, f: F)
+    where
+        F: FnOnce(&Metrics),
+    {
+        if let Some(ref metrics) = self.metrics {
+            f(metrics);
+        }
+    }
+
     /// sends a given command to the Server Query server and returns the answer as a String, or
     /// the error.
+    ///
+    /// The command waits for the RateLimit and is retried, if the server returns a flood error.
     pub fn send_command<C>(&mut self, command: &C) -> error::Result<String>
     where
         C: Command,
@@ -59,22 +201,53 @@ impl Connection {
             return Err(Error::from("no command"));
         }
 
+        let _span = trace::command_span(&command);
+        let start = Instant::now();
+        let mut retries = self.rate_limit.get_retries();
+        let mut sent;
+        let result = loop {
+            self.rate_limit.acquire();
+            sent = Instant::now();
+            let result = self.send_line(&command);
+            match result {
+                Err(ref e) if e.is_flooding() && retries > 0 => {
+                    retries -= 1;
+                    trace::flood_backoff(retries);
+                    self.observe(Metrics::flood_backoff);
+                    self.rate_limit.backoff();
+                }
+                result => break result,
+            }
+        };
+        // a retried command is counted once, the retries are counted as flood backoffs
+        self.observe(|metrics| {
+            metrics.command(&result);
+            metrics.latency(sent.elapsed());
+        });
+        trace::command_finished(&result, start.elapsed());
+        result
+    }
+
+    // writes a command and reads its answer.
+    fn send_line(&mut self, command: &str) -> error::Result<String> {
         writeln!(self.get_stream_mut(), "{}", command)?;
 
         self.get_stream_mut().flush()?;
 
-        let mut result = String::new();
+        self.read_reply()
+    }
+
+    // reads the answer of a command. Notifications are queued.
+    fn read_reply(&mut self) -> error::Result<String> {
+        let mut collector = ReplyCollector::new();
         loop {
-            let mut line = String::new();
-            let line = self.read_line(&mut line)?;
-            let ok = SQError::parse_is_ok(line)?;
-            if ok {
-                break;
+            let line = self.read_line()?;
+            match collector.push_line(&line) {
+                Line::Notification(notification) => self.queue_notification(notification),
+                Line::Reply(result) => return result,
+                Line::Partial => {}
             }
-            result += line; // + "\n";
         }
-
-        Ok(result)
     }
 
     pub fn send_command_to_map<C>(&mut self, command: &C) -> error::Result<StringMap>
@@ -85,6 +258,39 @@ impl Connection {
         Ok(to_map(&result))
     }
 
+    /// sends a given command to the server and parses the answer into the requested type.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use sqlib::connection::Connection;
+    /// use sqlib::map::StringMap;
+    ///
+    /// let mut conn = Connection::new("127.0.0.1:10011").unwrap();
+    /// let whoami: StringMap = conn.query(&"whoami").unwrap();
+    /// let _: () = conn.query(&"use 1").unwrap();
+    /// let servers: Vec<StringMap> = conn.query(&"serverlist").unwrap();
+    /// ```
+    pub fn query<T, C>(&mut self, command: &C) -> error::Result<T>
+    where
+        T: FromResponse,
+        C: Command,
+    {
+        let result = self.send_command(command)?;
+        T::from_response(&result)
+    }
+
+    /// like `query`, but returns an error if a value of the answer can not be parsed.
+    pub fn query_strict<T, C>(&mut self, command: &C) -> error::Result<T>
+    where
+        T: FromResponse,
+        C: Command,
+    {
+        let result = self.send_command(command)?;
+        T::from_response_strict(&result)
+    }
+
+    /// sends the commands one after another and returns all answers or the first error. See
+    /// `send_batch` to send them without waiting for every answer.
     pub fn send_command_vec<C>(&mut self, commands: C) -> error::Result<Vec<String>>
     where
         C: IntoIterator,
@@ -98,10 +304,237 @@ impl Connection {
         Ok(results)
     }
 
+    /// sends all commands without waiting for the answers in between and returns the answer or
+    /// the error of every command in the order of the commands.
+    ///
+    /// Unlike `send_command_vec` a failed command does not stop the others. At most
+    /// `PIPELINE_DEPTH` commands are sent ahead of their answers and every command waits for
+    /// the RateLimit. Commands, that the server refuses because of flooding, are sent again in
+    /// the end. If the connection fails, the remaining commands return an error.
+    ///
+    /// # Example
+    /// ```
+    /// use sqlib::connection::Connection;
+    /// use sqlib::testing::{MockServer, Reply};
+    ///
+    /// let server = MockServer::start().unwrap();
+    /// server.on("whoami", Reply::ok("virtualserver_id=1 client_id=5"));
+    ///
+    /// let mut conn = Connection::new(&server.addr()).unwrap();
+    /// let results = conn.send_batch(vec!["whoami", "unknown", "use sid=1"]);
+    ///
+    /// assert_eq!(results.len(), 3);
+    /// assert_eq!(results[0].as_ref().unwrap().trim(), "virtualserver_id=1 client_id=5");
+    /// assert!(results[1].as_ref().unwrap_err().is_sq());
+    /// assert!(results[2].is_ok());
+    /// ```
+    pub fn send_batch<C>(&mut self, commands: C) -> Vec<error::Result<String>>
+    where
+        C: IntoIterator,
+        C::Item: Command,
+    {
+        let commands: Vec<String> = commands.into_iter().map(|cmd| cmd.string()).collect();
+        let _span = trace::batch_span(commands.len());
+        let mut results: Vec<Option<error::Result<String>>> = Vec::new();
+        let mut pending = Vec::new();
+        for (i, command) in commands.iter().enumerate() {
+            if command.is_empty() {
+                results.push(Some(Err(Error::from("no command"))));
+            } else {
+                results.push(None);
+                pending.push(i);
+            }
+        }
+
+        let mut retries = self.rate_limit.get_retries();
+        while self.pipeline(&commands, &pending, &mut results) {
+            pending.retain(|&i| match results[i] {
+                Some(Err(ref e)) => e.is_flooding(),
+                _ => false,
+            });
+            if pending.is_empty() || retries == 0 {
+                break;
+            }
+            retries -= 1;
+            trace::flood_backoff(retries);
+            self.observe(Metrics::flood_backoff);
+            self.rate_limit.backoff();
+        }
+
+        results
+            .into_iter()
+            .map(|result| {
+                result.unwrap_or_else(|| {
+                    Err(Error::from(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        "the connection failed before the answer",
+                    )))
+                })
+            })
+            .collect()
+    }
+
+    // sends the commands with the given indices and stores their answers. It returns false, if
+    // the connection failed.
+    fn pipeline(
+        &mut self,
+        commands: &[String],
+        indices: &[usize],
+        results: &mut [Option<error::Result<String>>],
+    ) -> bool {
+        let mut written = 0;
+        for (read, &i) in indices.iter().enumerate() {
+            while written < indices.len() && written - read < PIPELINE_DEPTH {
+                // every command is written as soon as the RateLimit allows it, so the server
+                // never gets more commands at once than the RateLimit
+                self.rate_limit.acquire();
+                let sent = writeln!(self.get_stream_mut(), "{}", commands[indices[written]])
+                    .and_then(|_| self.get_stream_mut().flush());
+                if let Err(e) = sent {
+                    results[i] = Some(Err(Error::from(e)));
+                    return false;
+                }
+                written += 1;
+            }
+
+            let result = self.read_reply();
+            self.observe(|metrics| metrics.command(&result));
+            let failed = match result {
+                Err(ref e) => !e.is_sq(),
+                Ok(_) => false,
+            };
+            results[i] = Some(result);
+            if failed {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// registers for the notifications of an event (`server`, `channel`, `textserver`,
+    /// `textchannel`, `textprivate` or `tokenused`). The `channel` event needs a channel id, 0
+    /// means all channels.
+    pub fn register_notifications(&mut self, event: &str, id: Option<i64>) -> error::Result<()> {
+        let cmd = match id {
+            Some(id) => format!("servernotifyregister event={} id={}", event, id),
+            None => format!("servernotifyregister event={}", event),
+        };
+        self.send_command(&cmd).map(|_| ())
+    }
+
+    /// unregisters from all notifications.
+    pub fn unregister_notifications(&mut self) -> error::Result<()> {
+        self.send_command(&"servernotifyunregister").map(|_| ())
+    }
+
+    /// returns all notifications, that were received while waiting for the answers of commands,
+    /// without reading from the server.
+    pub fn take_notifications(&mut self) -> Vec<Notification> {
+        self.notifications.drain(..).collect()
+    }
+
+    /// returns the next notification and waits for it, if none was received yet.
+    pub fn wait_notification(&mut self) -> error::Result<Notification> {
+        loop {
+            if let Some(notification) = self.notifications.pop_front() {
+                return Ok(notification);
+            }
+            self.read_notification()?;
+        }
+    }
+
+    /// like `wait_notification`, but it returns `Ok(None)`, if no notification was received
+    /// within the timeout.
+    pub fn wait_notification_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> error::Result<Option<Notification>> {
+        if let Some(notification) = self.notifications.pop_front() {
+            return Ok(Some(notification));
+        }
+        self.get_stream_mut().set_read_timeout(Some(timeout))?;
+        let result = self.read_notification();
+        self.get_stream_mut().set_read_timeout(None)?;
+        match result {
+            Ok(()) => Ok(self.notifications.pop_front()),
+            Err(Error::Io(ref e))
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    // reads a line and queues it, if it is a notification. Other lines are dropped, because no
+    // command is waiting for an answer.
+    fn read_notification(&mut self) -> error::Result<()> {
+        let line = self.read_line()?;
+        if let Some(notification) = Notification::parse(&line) {
+            self.queue_notification(notification);
+        }
+        Ok(())
+    }
+
+    // queues a notification and drops the oldest ones, if the queue is full.
+    fn queue_notification(&mut self, notification: Notification) {
+        trace::notification(&notification);
+        self.observe(Metrics::notification);
+        while !self.notifications.is_empty()
+            && self.notifications.len() >= self.notification_capacity
+        {
+            self.notifications.pop_front();
+            self.dropped_notifications += 1;
+        }
+        if self.notification_capacity > 0 {
+            self.notifications.push_back(notification);
+        } else {
+            self.dropped_notifications += 1;
+        }
+    }
+
+    /// sets how many notifications are queued, until they are taken. If the queue is full, the
+    /// oldest notification is dropped. The default is `DEFAULT_NOTIFICATION_CAPACITY`.
+    ///
+    /// # Example
+    /// ```
+    /// use sqlib::connection::Connection;
+    /// use sqlib::testing::{MockServer, Reply};
+    ///
+    /// let server = MockServer::start().unwrap();
+    /// server.on("whoami", Reply::ok("virtualserver_id=1"));
+    ///
+    /// let mut conn = Connection::new(&server.addr()).unwrap();
+    /// conn.set_notification_capacity(2);
+    /// conn.register_notifications("server", None).unwrap();
+    /// for clid in 1..4 {
+    ///     server.notify(&format!("notifyclientleftview cfid=1 ctid=0 clid={}", clid));
+    /// }
+    /// // the notifications are read while waiting for the answer
+    /// conn.send_command(&"whoami").unwrap();
+    ///
+    /// let queued = conn.take_notifications();
+    /// assert_eq!(queued.len(), 2);
+    /// assert_eq!(queued[0].get("clid"), Some("2"));
+    /// assert_eq!(conn.dropped_notifications(), 1);
+    /// ```
+    pub fn set_notification_capacity(&mut self, capacity: usize) {
+        self.notification_capacity = capacity;
+        while self.notifications.len() > capacity {
+            self.notifications.pop_front();
+            self.dropped_notifications += 1;
+        }
+    }
+
+    /// returns the number of notifications, that were dropped, because the queue was full.
+    pub fn dropped_notifications(&self) -> u64 {
+        self.dropped_notifications
+    }
+
     /// sends the quit command to the server and shuts the Connection down.
     pub fn quit(&mut self) -> error::Result<()> {
         self.send_command(&"quit")?;
-        self.conn.get_ref().shutdown(net::Shutdown::Both)?;
+        self.get_stream_mut().shutdown()?;
         Ok(())
     }
 
@@ -116,6 +549,132 @@ impl Connection {
             .map(|_| ())
     }
 
+    /// creates a new virtual server with the given properties and returns its id, port and the
+    /// initial privilege key.
+    ///
+    /// The properties need at least a name.
+    pub fn server_create(&mut self, properties: &ServerProperties) -> error::Result<CreatedServer> {
+        if properties.get_name().is_none() {
+            return Err(Error::from("a virtual server needs a name"));
+        }
+        self.query(&format!("servercreate {}", properties.params()))
+    }
+
+    /// starts the virtual server with the given id.
+    pub fn server_start(&mut self, id: u64) -> error::Result<()> {
+        self.send_command(&format!("serverstart sid={}", id))
+            .map(|_| ())
+    }
+
+    /// stops the virtual server with the given id.
+    pub fn server_stop(&mut self, id: u64) -> error::Result<()> {
+        self.send_command(&format!("serverstop sid={}", id))
+            .map(|_| ())
+    }
+
+    /// deletes the virtual server with the given id. The virtual server has to be stopped first.
+    pub fn server_delete(&mut self, id: u64) -> error::Result<()> {
+        self.send_command(&format!("serverdelete sid={}", id))
+            .map(|_| ())
+    }
+
+    /// changes the given properties of the selected virtual server.
+    pub fn server_edit(&mut self, properties: &ServerProperties) -> error::Result<()> {
+        if properties.is_empty() {
+            return Err(Error::from("no properties to edit"));
+        }
+        self.send_command(&format!("serveredit {}", properties.params()))
+            .map(|_| ())
+    }
+
+    /// adds a Server Query login with the given name for the client with the database id
+    /// `cldbid` on the selected virtual server and returns the generated password.
+    pub fn query_login_add(&mut self, name: &str, cldbid: i64) -> error::Result<String> {
+        let cmd = format!(
+            "queryloginadd client_login_name={} cldbid={}",
+            escape(name),
+            cldbid
+        );
+        let map = self.send_command_to_map(&cmd)?;
+        let pw = map
+            .get("client_login_password")
+            .ok_or("error at collecting client_login_password")?;
+        Ok(unescape(pw))
+    }
+
+    /// deletes the Server Query login of the client with the database id `cldbid`.
+    pub fn query_login_del(&mut self, cldbid: i64) -> error::Result<()> {
+        self.send_command(&format!("querylogindel cldbid={}", cldbid))
+            .map(|_| ())
+    }
+
+    /// lists the Server Query logins, that match the given filter.
+    ///
+    /// An empty result is returned as an empty Vec and not as an error.
+    pub fn query_login_list(
+        &mut self,
+        filter: &QueryLoginFilter,
+    ) -> error::Result<Vec<QueryLogin>> {
+        let cmd = format!("queryloginlist {}", filter.params());
+        match self.query(&cmd.trim_end()) {
+            Ok(logins) => Ok(logins),
+            Err(Error::SQ(ref e)) if e.id() == DATABASE_EMPTY_RESULT_SET => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// sets the Server Query login name of the own client (legacy login path, without a database
+    /// id) and returns the generated password.
+    pub fn client_set_server_query_login(&mut self, name: &str) -> error::Result<String> {
+        let cmd = format!(
+            "clientsetserverquerylogin client_login_name={}",
+            escape(name)
+        );
+        let map = self.send_command_to_map(&cmd)?;
+        let pw = map
+            .get("client_login_password")
+            .ok_or("error at collecting client_login_password")?;
+        Ok(unescape(pw))
+    }
+
+    /// creates an API key for the WebQuery interface and returns it together with its id.
+    ///
+    /// The key is valid for `lifetime` days, 0 means forever. Without a database id the key
+    /// belongs to the own client.
+    pub fn api_key_add(
+        &mut self,
+        scope: ApiKeyScope,
+        lifetime: u32,
+        cldbid: Option<i64>,
+    ) -> error::Result<ApiKey> {
+        let mut cmd = format!("apikeyadd scope={} lifetime={}", scope, lifetime);
+        if let Some(cldbid) = cldbid {
+            cmd.push_str(&format!(" cldbid={}", cldbid));
+        }
+        self.query(&cmd)
+    }
+
+    /// lists the API keys of the client with the database id `cldbid`, or of all clients.
+    ///
+    /// An empty result is returned as an empty Vec and not as an error.
+    pub fn api_key_list(&mut self, cldbid: Option<i64>) -> error::Result<Vec<ApiKey>> {
+        let cmd = match cldbid {
+            Some(cldbid) => format!("apikeylist cldbid={}", cldbid),
+            None => "apikeylist cldbid=*".to_string(),
+        };
+        match self.query(&cmd) {
+            Ok(keys) => Ok(keys),
+            Err(Error::SQ(ref e)) if e.id() == DATABASE_EMPTY_RESULT_SET => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// deletes the API key with the given id.
+    pub fn api_key_del(&mut self, id: u64) -> error::Result<()> {
+        self.send_command(&format!("apikeydel id={}", id))
+            .map(|_| ())
+    }
+
     /// tries to change the nickname of the Server Query client.
     pub fn change_nickname(&mut self, nickname: &str) -> error::Result<()> {
         let map = self.send_command_to_map(&"whoami")?;
@@ -127,11 +686,51 @@ impl Connection {
         Ok(())
     }
 
+    /// returns the information about the selected virtual server.
+    pub fn serverinfo(&mut self) -> error::Result<StringMap> {
+        self.query(&"serverinfo")
+    }
+
+    /// sends a text message to a client, the own channel or the selected virtual server.
+    pub fn send_text_message(
+        &mut self,
+        target: &TextMessageTarget,
+        msg: &str,
+    ) -> error::Result<()> {
+        self.send_command(&target.command(msg)).map(|_| ())
+    }
+
     /// sends the clientlist command to the server and parses the result.
     pub fn clientlist(&mut self) -> error::Result<ClientList> {
-        let s = self.send_command(&"clientlist")?;
-        let cl = s.parse()?;
-        Ok(cl)
+        self.query(&"clientlist")
+    }
+
+    /// sends the clientlist command with the given options and parses the result. Unlike
+    /// `clientlist_with_info` it needs a single command for all clients.
+    ///
+    /// # Example
+    /// ```
+    /// use sqlib::client::ClientListOptions;
+    /// use sqlib::connection::Connection;
+    /// use sqlib::testing::{MockServer, Reply};
+    ///
+    /// let server = MockServer::start().unwrap();
+    /// server.on(
+    ///     "clientlist",
+    ///     Reply::ok("clid=1 cid=1 client_database_id=2 client_nickname=test client_type=0 \
+    ///                client_away=1 client_away_message=brb client_servergroups=6,8"),
+    /// );
+    ///
+    /// let mut conn = Connection::new(&server.addr()).unwrap();
+    /// let clients = conn.clientlist_with(&ClientListOptions::new().away().groups()).unwrap();
+    ///
+    /// assert!(clients[0].is_away());
+    /// assert_eq!(clients[0].client_away_message, "brb");
+    /// assert_eq!(clients[0].servergroups(), vec![6, 8]);
+    /// assert_eq!(server.received()[0], "clientlist -away -groups");
+    /// ```
+    pub fn clientlist_with(&mut self, options: &ClientListOptions) -> error::Result<ClientList> {
+        self.query(&options.command())
     }
 
     /// # common errors
@@ -150,9 +749,7 @@ impl Connection {
 
     /// sends the channellist command to the server and parses the result.
     pub fn channellist(&mut self) -> error::Result<ChannelList> {
-        let s = self.send_command(&"channellist")?;
-        let cl = s.parse()?;
-        Ok(cl)
+        self.query(&"channellist")
     }
 
     /// # common errors
@@ -164,6 +761,16 @@ impl Connection {
         channels.merge_clients(&clients);
         Ok(channels)
     }
+
+    /// like `channellist_with_clients`, but the clients are listed with a single `clientlist`
+    /// command with the given options. The clients have no `connection_connected_time`, because
+    /// only `clientinfo` returns it.
+    pub fn channellist_with(&mut self, options: &ClientListOptions) -> error::Result<ChannelList> {
+        let clients = self.clientlist_with(options)?;
+        let mut channels = self.channellist()?;
+        channels.merge_clients(&clients);
+        Ok(channels)
+    }
 }
 
 impl fmt::Display for Connection {
@@ -0,0 +1,431 @@
+//! The de module contains a serde Deserializer for the Server Query format.
+//!
+//! An answer is a list of records, that are seperated by `|`, and every record is a map of
+//! `key=value` pairs. The values are unescaped by the Deserializer, so any struct, that derives
+//! `Deserialize`, can be read from an answer.
+//!
+//! - A sequence, like `Vec<T>`, contains all records of the answer.
+//! - A struct or a map is the first record of the answer.
+//! - A sequence inside a value is seperated by `,`, e.g. `client_servergroups=6,8`. The format
+//!   has no escape sequence for `,`, so the Serializer refuses items, that contain one, and
+//!   empty items are skipped.
+//! - An `Option` is `None`, if the key is missing or has no value. So `Some("")` is written as
+//!   `key=` and read back as `None`.
+//! - A `bool` is `1` or `0`.
+//! - If a key is given more than once, the last value is used.
+//!
+//! The `FromStr` implementations of the model types, like Client and Channel, do not use the
+//! Deserializer: they ignore invalid values instead of failing and behave the same with and
+//! without the `serde` feature.
+//!
+//! # Example
+//! ```
+//! #[macro_use]
+//! extern crate serde;
+//! extern crate sqlib;
+//!
+//! #[derive(Deserialize)]
+//! struct Client {
+//!     clid: i64,
+//!     client_nickname: String,
+//!     client_away_message: Option<String>,
+//!     client_servergroups: Vec<u32>,
+//! }
+//!
+//! # fn main() {
+//! let answer = "clid=1 client_nickname=John\\sDoe client_servergroups=6,8|clid=2 \
+//!               client_nickname=test client_away_message=afk client_servergroups=8";
+//! let clients: Vec<Client> = sqlib::from_str(answer).unwrap();
+//!
+//! assert_eq!(clients[0].client_nickname, "John Doe");
+//! assert_eq!(clients[0].client_away_message, None);
+//! assert_eq!(clients[0].client_servergroups, vec![6, 8]);
+//! assert_eq!(clients[1].client_away_message, Some("afk".to_string()));
+//! # }
+//! ```
+
+use error::{Error, Result};
+use escaping::unescape;
+use map::pairs;
+use response::records;
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+
+/// Deserializer reads the records of an answer of the server.
+#[derive(Debug, Clone, Copy)]
+pub struct Deserializer<'de> {
+    input: &'de str,
+}
+
+impl<'de> Deserializer<'de> {
+    /// creates a Deserializer for an answer without the `error` line.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(input: &'de str) -> Deserializer<'de> {
+        Deserializer { input }
+    }
+}
+
+/// deserializes an answer of the server, e.g. into a `Vec` of structs.
+pub fn from_str<'a, T>(s: &'a str) -> Result<T>
+where
+    T: de::Deserialize<'a>,
+{
+    T::deserialize(&mut Deserializer::from_str(s))
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(Records {
+            records: records(self.input),
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let record = records(self.input)
+            .next()
+            .ok_or_else(|| Error::from("empty response"))?;
+        RecordDeserializer { record }.deserialize_map(visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if records(self.input).next().is_none() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        unit_struct enum identifier
+    }
+}
+
+// the records of an answer
+struct Records<I> {
+    records: I,
+}
+
+impl<'de, I> de::SeqAccess<'de> for Records<I>
+where
+    I: Iterator<Item = &'de str>,
+{
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.records.next() {
+            Some(record) => seed.deserialize(RecordDeserializer { record }).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+// a single record
+struct RecordDeserializer<'de> {
+    record: &'de str,
+}
+
+impl<'de> de::Deserializer<'de> for RecordDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let all: Vec<_> = pairs(self.record).collect();
+        // the last value of a key is used, like in a StringMap
+        let pairs = all
+            .iter()
+            .enumerate()
+            .filter(|&(i, pair)| !all[i + 1..].iter().any(|other| other.0 == pair.0))
+            .map(|(_, &pair)| pair)
+            .collect::<Vec<_>>();
+        visitor.visit_map(Pairs {
+            pairs: pairs.into_iter(),
+            value: None,
+        })
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        unit unit_struct seq tuple tuple_struct struct enum identifier
+    }
+}
+
+// the pairs of a record
+struct Pairs<'de, I> {
+    pairs: I,
+    value: Option<(&'de str, Option<&'de str>)>,
+}
+
+impl<'de, I> de::MapAccess<'de> for Pairs<'de, I>
+where
+    I: Iterator<Item = (&'de str, Option<&'de str>)>,
+{
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.pairs.next() {
+            Some((key, value)) => {
+                self.value = Some((key, value));
+                seed.deserialize(BorrowedStrDeserializer::new(key))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some((key, value)) => seed.deserialize(ValueDeserializer { key, value }),
+            None => Err(Error::from("a value without a key")),
+        }
+    }
+}
+
+// the escaped value of a single key; a key without a value has no value
+struct ValueDeserializer<'de> {
+    key: &'de str,
+    value: Option<&'de str>,
+}
+
+impl<'de> ValueDeserializer<'de> {
+    fn raw(&self) -> &'de str {
+        self.value.unwrap_or("")
+    }
+
+    fn parse<T: ::std::str::FromStr>(&self) -> Result<T> {
+        self.raw()
+            .parse()
+            .map_err(|_| Error::invalid_value(self.key, self.raw()))
+    }
+}
+
+// implements the deserialize method for a number type
+macro_rules! deserialize_number {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                visitor.$visit(self.parse()?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.raw() {
+            "1" | "true" => visitor.visit_bool(true),
+            "0" | "false" => visitor.visit_bool(false),
+            value => Err(Error::invalid_value(self.key, value)),
+        }
+    }
+
+    deserialize_number! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let value = unescape(self.raw());
+        let mut chars = value.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(Error::invalid_value(self.key, self.raw())),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let raw = self.raw();
+        // values without an escape sequence are borrowed
+        if raw.contains('\\') {
+            visitor.visit_string(unescape(raw))
+        } else {
+            visitor.visit_borrowed_str(raw)
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let raw = self.raw();
+        if raw.contains('\\') {
+            visitor.visit_byte_buf(unescape(raw).into_bytes())
+        } else {
+            visitor.visit_borrowed_bytes(raw.as_bytes())
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.raw().is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let key = self.key;
+        let items = self
+            .raw()
+            .split(',')
+            .filter(|item| !item.is_empty())
+            .map(move |item| ValueDeserializer {
+                key,
+                value: Some(item),
+            });
+        visitor.visit_seq(de::value::SeqDeserializer::new(items))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let variant: de::value::StringDeserializer<Error> =
+            unescape(self.raw()).into_deserializer();
+        visitor.visit_enum(variant)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        map struct identifier
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for ValueDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
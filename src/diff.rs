@@ -0,0 +1,176 @@
+//! The diff module compares two snapshots of a ChannelList or ClientList and returns the
+//! changes between them.
+//!
+//! Clients are identified by their client id and channels by their channel id, like their `Eq`
+//! implementations do.
+//!
+//! # Example
+//! ```
+//! use sqlib::channel::{Channel, ChannelList};
+//! use sqlib::client::Client;
+//! use sqlib::diff::{diff, Change};
+//!
+//! let mut lobby = Channel::new(1, "lobby".to_string());
+//! let games = Channel::new(2, "games".to_string());
+//! let old = ChannelList::from(vec![lobby.clone(), games.clone()]);
+//!
+//! lobby.add_client(Client::new(5, "test".to_string()));
+//! let new = ChannelList::from(vec![lobby, games]);
+//!
+//! let changes = diff(&old, &new);
+//!
+//! assert_eq!(changes.len(), 1);
+//! match changes[0] {
+//!     Change::ClientJoined { ref client } => {
+//!         assert_eq!(client.client_nickname, "test");
+//!         assert_eq!(client.cid, 1);
+//!     }
+//!     _ => panic!("unexpected change"),
+//! }
+//! ```
+
+use channel::{Channel, ChannelList};
+use client::{Client, ClientList};
+use std::collections::BTreeMap;
+
+/// Change is a single difference between two snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Change {
+    /// a client connected; its cid is the channel it joined
+    ClientJoined { client: Client },
+    /// a client disconnected; its cid is the channel it left
+    ClientLeft { client: Client },
+    /// a client moved from one channel to another
+    ClientMoved { client: Client, from: i64, to: i64 },
+    /// a client changed its nickname
+    ClientRenamed {
+        client: Client,
+        old_nickname: String,
+    },
+    /// a channel was created
+    ChannelAdded { channel: Channel },
+    /// a channel was deleted
+    ChannelRemoved { channel: Channel },
+    /// a channel changed its name
+    ChannelRenamed { channel: Channel, old_name: String },
+}
+
+/// returns the changes between two ChannelLists, including the clients of the channels.
+///
+/// The cid of a client is taken from the channel it is in. The changes are ordered: added and
+/// renamed channels, then the client changes, then removed channels.
+pub fn diff(old: &ChannelList, new: &ChannelList) -> Vec<Change> {
+    let old_channels = by_cid(old);
+    let new_channels = by_cid(new);
+    let mut changes = Vec::new();
+
+    for (cid, channel) in &new_channels {
+        match old_channels.get(cid) {
+            None => changes.push(Change::ChannelAdded {
+                channel: (*channel).clone(),
+            }),
+            Some(old_channel) if old_channel.channel_name != channel.channel_name => {
+                changes.push(Change::ChannelRenamed {
+                    channel: (*channel).clone(),
+                    old_name: old_channel.channel_name.clone(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+
+    changes.extend(diff_clients(&clients_of(old), &clients_of(new)));
+
+    for (cid, channel) in &old_channels {
+        if !new_channels.contains_key(cid) {
+            changes.push(Change::ChannelRemoved {
+                channel: (*channel).clone(),
+            });
+        }
+    }
+
+    changes
+}
+
+/// returns the changes between two ClientLists. Only client changes are returned.
+///
+/// # Example
+/// ```
+/// use sqlib::client::{Client, ClientList};
+/// use sqlib::diff::{diff_clients, Change};
+///
+/// let old = ClientList::from(vec![Client::new(1, "old".to_string())]);
+/// let new = ClientList::from(vec![Client::new(1, "new".to_string())]);
+///
+/// let changes = diff_clients(&old, &new);
+///
+/// assert_eq!(
+///     changes,
+///     vec![Change::ClientRenamed {
+///         client: Client::new(1, "new".to_string()),
+///         old_nickname: "old".to_string(),
+///     }]
+/// );
+/// ```
+pub fn diff_clients(old: &ClientList, new: &ClientList) -> Vec<Change> {
+    let old_clients = by_clid(old);
+    let new_clients = by_clid(new);
+    let mut changes = Vec::new();
+
+    for (clid, client) in &old_clients {
+        if !new_clients.contains_key(clid) {
+            changes.push(Change::ClientLeft {
+                client: (*client).clone(),
+            });
+        }
+    }
+
+    for (clid, client) in &new_clients {
+        let old_client = match old_clients.get(clid) {
+            None => {
+                changes.push(Change::ClientJoined {
+                    client: (*client).clone(),
+                });
+                continue;
+            }
+            Some(old_client) => old_client,
+        };
+        if old_client.cid != client.cid {
+            changes.push(Change::ClientMoved {
+                client: (*client).clone(),
+                from: old_client.cid,
+                to: client.cid,
+            });
+        }
+        if old_client.client_nickname != client.client_nickname {
+            changes.push(Change::ClientRenamed {
+                client: (*client).clone(),
+                old_nickname: old_client.client_nickname.clone(),
+            });
+        }
+    }
+
+    changes
+}
+
+fn by_cid(channels: &ChannelList) -> BTreeMap<i64, &Channel> {
+    channels.iter().map(|c| (c.cid, c)).collect()
+}
+
+fn by_clid(clients: &ClientList) -> BTreeMap<i64, &Client> {
+    clients.iter().map(|c| (c.clid, c)).collect()
+}
+
+// collects the clients of all channels and sets their cid to the channel they are in.
+fn clients_of(channels: &ChannelList) -> ClientList {
+    let mut clients = Vec::new();
+    for channel in channels.iter() {
+        for client in &channel.clients {
+            let mut client = client.clone();
+            client.cid = channel.cid;
+            clients.push(client);
+        }
+    }
+    ClientList::from(clients)
+}
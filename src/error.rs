@@ -1,6 +1,7 @@
 //! error provides newtypes and Error's for sqlib.
 
 use escaping::unescape;
+use rustc_serialize::json;
 use std::convert::From;
 use std::error::{self, Error as Err};
 use std::fmt::{self, Display};
@@ -12,6 +13,12 @@ use std::sync::PoisonError;
 /// The standart result type of sqlib.
 pub type Result<T> = result::Result<T, Error>;
 
+/// The error id the server returns, if a list command has no results.
+pub const DATABASE_EMPTY_RESULT_SET: u32 = 1281;
+
+/// The error id the server returns, if a client sends too many commands.
+pub const CLIENT_IS_FLOODING: u32 = 524;
+
 /// A SQError contains a TS3 Server Query error.
 ///
 /// # Example
@@ -27,16 +34,34 @@ pub type Result<T> = result::Result<T, Error>;
 /// };
 /// assert_eq!(0, err.id());
 /// ```
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "SQErrorFields"))]
 pub struct SQError {
     id: u32,
     msg: String,
+    #[cfg_attr(feature = "serde", serde(skip_serializing))]
     full_msg: String,
 }
 
+// the serialized fields of a SQError, the full message is created again
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct SQErrorFields {
+    id: u32,
+    msg: String,
+}
+
+#[cfg(feature = "serde")]
+impl From<SQErrorFields> for SQError {
+    fn from(fields: SQErrorFields) -> SQError {
+        SQError::new(fields.id, fields.msg)
+    }
+}
+
 // helping function for SQError::parse
 fn is_seperator(c: char) -> bool {
-    c == '=' || c.is_whitespace()
+    c.is_ascii_whitespace()
 }
 
 impl SQError {
@@ -95,31 +120,41 @@ impl SQError {
     }
 
     /// try to parse a String to a SQError
+    ///
+    /// # Example
+    /// ```
+    /// use sqlib::error::SQError;
+    ///
+    /// let err = SQError::parse("error id=1538 msg=invalid\\sparameter\\sname=value").unwrap();
+    ///
+    /// assert_eq!(err.id(), 1538);
+    /// assert_eq!(err.msg(), "invalid parameter name=value");
+    /// assert!(SQError::parse("error id=x msg=ok").is_none());
+    /// ```
     pub fn parse(s: &str) -> Option<SQError> {
         // the str shouldn't be trimmed, because a real error is without
         // whitespace in the beginning
-        let parts: Vec<&str> = s.splitn(6, is_seperator).collect();
-        if parts.len() < 5 {
+        // the message may contain an unescaped `=`, so only the whitespace seperates the parts
+        let parts: Vec<&str> = s.splitn(4, is_seperator).collect();
+        if parts.len() < 3 {
             return None;
         }
         if parts[0] != "error" {
             return None;
         }
-        if parts[1] != "id" {
-            return None;
-        }
-        let id_result = parts[2].parse::<u32>();
-        let id = match id_result {
-            Err(_) => {
+        let id = match parts[1].strip_prefix("id=").map(str::parse::<u32>) {
+            Some(Ok(val)) => val,
+            _ => {
                 return None;
             }
-            Ok(val) => val,
         };
-        if parts[3] != "msg" {
-            return None;
-        }
-        let msg = parts[4].to_string().clone();
-        Some(SQError::new(id, unescape(&msg)))
+        let msg = match parts[2].strip_prefix("msg=") {
+            Some(msg) => msg,
+            None => {
+                return None;
+            }
+        };
+        Some(SQError::new(id, unescape(msg)))
     }
 
     pub fn id(&self) -> u32 {
@@ -158,11 +193,18 @@ pub enum Error {
     Io(io::Error),
     /// server query error messages
     SQ(SQError),
+    /// a value of an answer, that could not be parsed
+    Parse(String),
     /// other errors
     Other(String),
 }
 
 impl Error {
+    /// creates a Parse error for the value of the given key.
+    pub fn invalid_value(key: &str, value: &str) -> Error {
+        Error::Parse(format!("invalid value {:?} for the key {}", value, key))
+    }
+
     pub fn is_io(&self) -> bool {
         match *self {
             Error::Io(_) => true,
@@ -177,6 +219,18 @@ impl Error {
         }
     }
 
+    pub fn is_parse(&self) -> bool {
+        matches!(*self, Error::Parse(_))
+    }
+
+    /// checks if the server refused a command because of flooding.
+    pub fn is_flooding(&self) -> bool {
+        match *self {
+            Error::SQ(ref err) => err.id() == CLIENT_IS_FLOODING,
+            _ => false,
+        }
+    }
+
     pub fn is_other(&self) -> bool {
         match *self {
             Error::Other(_) => true,
@@ -185,11 +239,24 @@ impl Error {
     }
 }
 
+impl Clone for Error {
+    /// clones the Error. An io::Error keeps its kind and message, but not its source.
+    fn clone(&self) -> Error {
+        match *self {
+            Error::Io(ref err) => Error::Io(io::Error::new(err.kind(), err.to_string())),
+            Error::SQ(ref err) => Error::SQ(err.clone()),
+            Error::Parse(ref s) => Error::Parse(s.clone()),
+            Error::Other(ref s) => Error::Other(s.clone()),
+        }
+    }
+}
+
 impl error::Error for Error {
     fn description(&self) -> &str {
         match *self {
             Error::Io(ref err) => err.description(),
             Error::SQ(ref err) => err.description(),
+            Error::Parse(ref s) => s,
             Error::Other(ref s) => s,
         }
     }
@@ -197,7 +264,12 @@ impl error::Error for Error {
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.description())
+        match *self {
+            // the description of an io::Error is deprecated and does not contain the message
+            Error::Io(ref err) => write!(f, "{}", err),
+            Error::SQ(ref err) => write!(f, "{}", err),
+            Error::Parse(ref s) | Error::Other(ref s) => write!(f, "{}", s),
+        }
     }
 }
 
@@ -225,12 +297,39 @@ impl From<SQError> for Error {
     }
 }
 
+impl From<json::EncoderError> for Error {
+    fn from(err: json::EncoderError) -> Error {
+        Error::Other(format!("{}", err))
+    }
+}
+
 impl From<AddrParseError> for Error {
     fn from(err: AddrParseError) -> Error {
         Error::Other(err.description().to_string())
     }
 }
 
+#[cfg(feature = "serde")]
+impl ::serde::de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error::Parse(msg.to_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ::serde::ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error::Other(msg.to_string())
+    }
+}
+
+#[cfg(feature = "ssh")]
+impl From<::ssh2::Error> for Error {
+    fn from(err: ::ssh2::Error) -> Error {
+        Error::Io(io::Error::from(err))
+    }
+}
+
 impl<T> From<PoisonError<T>> for Error {
     fn from(err: PoisonError<T>) -> Error {
         Error::Other(format!("{}", err))
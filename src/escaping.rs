@@ -14,6 +14,8 @@
 //! assert_eq!(unescaped_test, unescaped);
 //! ```
 
+use std::borrow::Cow;
+
 const ESCAPE_CHARS: [(char, &str); 11] = [
     ('\\', r"\\"),
     (' ', r"\s"),
@@ -42,9 +44,12 @@ const ESCAPE_CHARS: [(char, &str); 11] = [
 /// assert_eq!(s, escaped);
 /// ```
 pub fn escape(s: &str) -> String {
-    let mut new_string = s.to_string();
-    for &(from, to) in &ESCAPE_CHARS {
-        new_string = new_string.replace(from, to);
+    let mut new_string = String::with_capacity(s.len());
+    for c in s.chars() {
+        match ESCAPE_CHARS.iter().find(|&&(from, _)| from == c) {
+            Some(&(_, to)) => new_string.push_str(to),
+            None => new_string.push(c),
+        }
     }
     new_string
 }
@@ -61,11 +66,52 @@ pub fn escape(s: &str) -> String {
 /// let s = unescape(&escaped);
 ///
 /// assert_eq!(s, unescaped);
+/// assert_eq!(unescape("\\\\s"), "\\s");
 /// ```
 pub fn unescape(s: &str) -> String {
-    let mut new_string = s.to_string();
-    for &(to, from) in &ESCAPE_CHARS {
-        new_string = new_string.replace(from, &to.to_string());
+    // a single pass, so that an escaped backslash is never read as the start of another escape
+    // sequence, e.g. `\\s` is `\s` and not `\ `
+    let mut new_string = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            new_string.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(next) => {
+                let escaped = ESCAPE_CHARS
+                    .iter()
+                    .find(|&&(_, from)| from[1..].starts_with(next));
+                match escaped {
+                    Some(&(to, _)) => new_string.push(to),
+                    // unknown escape sequences are kept
+                    None => {
+                        new_string.push('\\');
+                        new_string.push(next);
+                    }
+                }
+            }
+            None => new_string.push('\\'),
+        }
     }
     new_string
 }
+
+/// like `unescape`, but a string without an escape sequence is borrowed instead of copied.
+///
+/// # Example
+/// ```
+/// use sqlib::escaping::unescape_cow;
+/// use std::borrow::Cow;
+///
+/// assert_eq!(unescape_cow("hello"), Cow::Borrowed("hello"));
+/// assert_eq!(unescape_cow("hello\\sworld"), "hello world");
+/// ```
+pub fn unescape_cow(s: &str) -> Cow<'_, str> {
+    if s.contains('\\') {
+        Cow::Owned(unescape(s))
+    } else {
+        Cow::Borrowed(s)
+    }
+}
@@ -1,13 +1,69 @@
+#[cfg(feature = "async")]
+extern crate futures_core;
 extern crate rustc_serialize;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(feature = "tls")]
+extern crate rustls;
+#[cfg(feature = "ssh")]
+extern crate ssh2;
+#[macro_use]
+extern crate sqlib_derive;
 
+// lets the derive macros refer to `::sqlib` inside of this crate, too.
+extern crate self as sqlib;
+
+#[cfg(feature = "async")]
+extern crate tokio;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+#[cfg(feature = "tls")]
+extern crate webpki_roots;
+
+pub use sqlib_derive::FromStringMap;
+
+#[cfg(feature = "serde")]
+pub use de::{from_str, Deserializer};
+#[cfg(feature = "serde")]
+pub use ser::{to_string, Serializer};
+
+pub mod apikey;
+#[cfg(feature = "async")]
+pub mod async_connection;
 pub mod channel;
 pub mod client;
+pub mod clientquery;
 pub mod command;
 pub mod connection;
+#[cfg(feature = "serde")]
+pub mod de;
+pub mod diff;
 pub mod error;
 pub mod escaping;
 pub mod map;
+pub mod metrics;
+pub mod notification;
+pub mod pool;
 pub mod prelude;
+pub mod querylogin;
+pub mod ratelimit;
+pub mod recording;
+pub mod response;
+#[cfg(feature = "serde")]
+pub mod ser;
+pub mod server;
+pub mod shared;
+#[cfg(feature = "ssh")]
+pub mod ssh;
+pub mod state;
+pub mod testing;
+pub mod textmessage;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod trace;
+pub mod transport;
+pub mod webquery;
 
 // pub use client::{Client, ClientList};
 // pub use channel::{Channel, ChannelList};
@@ -12,41 +12,325 @@
 //! assert_eq!(integer, 2);
 //! ```
 
+use error::{Error, Result};
+use escaping::unescape_cow;
+use response::records;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fmt;
+use std::iter::FromIterator;
+use std::slice;
 use std::str::FromStr;
 
 /// A small newtype for a HashMap of Strings.
 pub type StringMap = HashMap<String, String>;
 
+/// A trait for types, that can be created from a StringMap.
+///
+/// It can be derived with `#[derive(FromStringMap)]`, see the crate documentation.
+///
+/// # Example
+/// ```
+/// use sqlib::map::{to_map, update_from_map, FromStringMap, StringMap};
+///
+/// #[derive(Default)]
+/// struct Server {
+///     sid: u64,
+/// }
+///
+/// impl FromStringMap for Server {
+///     fn from_map(map: &StringMap) -> Server {
+///         let mut server = Server::default();
+///         server.mut_from_map(map);
+///         server
+///     }
+///
+///     fn mut_from_map(&mut self, map: &StringMap) {
+///         update_from_map(map, "sid", &mut self.sid);
+///     }
+/// }
+///
+/// let server = Server::from_map(&to_map("sid=3"));
+/// assert_eq!(server.sid, 3);
+/// ```
+pub trait FromStringMap {
+    /// creates Self from a given map.
+    fn from_map(map: &StringMap) -> Self
+    where
+        Self: Sized;
+
+    /// mutates self from a given map. Fields, whose keys are missing in the map, stay unchanged.
+    fn mut_from_map(&mut self, map: &StringMap);
+
+    /// creates Self from a given map, but returns an error if a value can not be parsed.
+    ///
+    /// The default implementation can not detect invalid values and falls back to `from_map`.
+    fn try_from_map(map: &StringMap) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self::from_map(map))
+    }
+
+    /// mutates self from a given map, but returns an error if a value can not be parsed.
+    ///
+    /// The default implementation can not detect invalid values and falls back to
+    /// `mut_from_map`.
+    fn try_mut_from_map(&mut self, map: &StringMap) -> Result<()> {
+        self.mut_from_map(map);
+        Ok(())
+    }
+}
+
+/// A trait for types, that can be written as the `key=value` parameters of a command.
+///
+/// It is derived together with `FromStringMap`.
+///
+/// # Example
+/// ```
+/// #[macro_use]
+/// extern crate sqlib;
+///
+/// use sqlib::map::{to_map, FromStringMap, ToParams};
+///
+/// #[derive(FromStringMap)]
+/// struct Ban {
+///     banid: i64,
+///     #[sqlib(escape)]
+///     reason: String,
+///     #[sqlib(rename = "lastnickname", escape)]
+///     nickname: Option<String>,
+///     #[sqlib(default = "default_duration")]
+///     duration: u64,
+/// }
+///
+/// fn default_duration() -> u64 {
+///     60
+/// }
+///
+/// # fn main() {
+/// let ban = Ban::from_map(&to_map("banid=1 reason=too\\sloud"));
+///
+/// assert_eq!(ban.reason, "too loud");
+/// assert_eq!(ban.nickname, None);
+/// assert_eq!(ban.duration, 60);
+/// assert_eq!(ban.to_params(), "banid=1 reason=too\\sloud duration=60");
+/// # }
+/// ```
+pub trait ToParams {
+    /// creates the escaped `key=value` parameters, seperated by whitespace.
+    fn to_params(&self) -> String;
+}
+
+impl FromStringMap for StringMap {
+    fn from_map(map: &StringMap) -> StringMap {
+        map.clone()
+    }
+
+    fn mut_from_map(&mut self, map: &StringMap) {
+        self.extend(map.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+}
+
 /// creates a new StringMap from a &str.
 ///
+/// A key without a value, like a flag, is left out; use `Record::is_flag` to check for it. If a
+/// key is given more than once, the last value is kept. Use `Record` to keep all of them.
+///
 /// # Example
 /// ```
-/// use sqlib::map::to_map;
+/// use sqlib::map::{to_map, Record};
 ///
-/// let string = " key1=value1 key2=value2 ";
+/// let string = " key1=value1 key2=value2 key3 ";
 /// let map = to_map(string);
 ///
 /// assert_eq!(map.get("key1").unwrap(), "value1");
 /// assert_eq!(map.get("key2").unwrap(), "value2");
+/// assert!(!map.contains_key("key3"));
+/// assert!(Record::parse(string).is_flag("key3"));
 /// ```
 pub fn to_map(string: &str) -> StringMap {
-    let pair_seperator = char::is_whitespace;
+    Record::parse(string).to_map()
+}
+
+// splits a single `key=value` or `key` token.
+fn split_pair(pair: &str) -> (&str, Option<&str>) {
     let key_value_seperator = '=';
-    let mut map = HashMap::new();
+    let mut kv = pair.splitn(2, key_value_seperator);
+    let key = kv.next().unwrap_or("");
+    (key, kv.next())
+}
+
+// splits a single record into its `key=value` and `key` tokens. Only ASCII whitespace seperates
+// pairs, other whitespace is not escaped by the server.
+pub(crate) fn pairs(string: &str) -> impl Iterator<Item = (&str, Option<&str>)> {
+    string
+        .split(|c: char| c.is_ascii_whitespace())
+        .filter(|pair| !pair.is_empty())
+        .map(split_pair)
+}
+
+/// A Record contains all `key=value` pairs of a single record of an answer in their original
+/// order.
+///
+/// Unlike a StringMap it keeps keys without a value and keys, that are given more than once. The
+/// values stay escaped, like in a StringMap.
+///
+/// # Example
+/// ```
+/// use sqlib::map::Record;
+///
+/// let record = Record::parse("cid=1 client_away_message cid=2");
+///
+/// assert_eq!(record.len(), 3);
+/// assert_eq!(record.get("cid"), Some("2"));
+/// assert_eq!(record.get_all("cid"), vec!["1", "2"]);
+/// assert!(record.is_flag("client_away_message"));
+/// assert_eq!(record.to_string(), "cid=1 client_away_message cid=2");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Record(Vec<(String, Option<String>)>);
+
+impl Record {
+    /// creates an empty Record.
+    pub fn new() -> Record {
+        Record::default()
+    }
+
+    /// parses a single record. It never fails, every token is kept.
+    pub fn parse(string: &str) -> Record {
+        pairs(string)
+            .map(|(key, value)| (key.to_string(), value.map(|v| v.to_string())))
+            .collect()
+    }
+
+    /// parses all records of an answer, that are seperated by `|`.
+    ///
+    /// # Example
+    /// ```
+    /// use sqlib::map::Record;
+    ///
+    /// let records = Record::parse_list("clid=1|clid=2");
+    ///
+    /// assert_eq!(records.len(), 2);
+    /// assert_eq!(records[1].get("clid"), Some("2"));
+    /// ```
+    pub fn parse_list(string: &str) -> Vec<Record> {
+        records(string).map(Record::parse).collect()
+    }
+
+    /// appends a key with an optional value.
+    pub fn push(&mut self, key: &str, value: Option<&str>) {
+        self.0.push((key.to_string(), value.map(|v| v.to_string())));
+    }
+
+    /// returns the last value of the key. A key without a value has the value "".
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.iter()
+            .rev()
+            .find(|pair| pair.0 == key)
+            .map(|pair| pair.1.as_deref().unwrap_or(""))
+    }
+
+    /// returns all values of the key in their original order.
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        self.iter()
+            .filter(|pair| pair.0 == key)
+            .map(|pair| pair.1.as_deref().unwrap_or(""))
+            .collect()
+    }
+
+    /// checks if the key is in the Record, with or without a value.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.iter().any(|pair| pair.0 == key)
+    }
 
-    let pairs = string.split(pair_seperator);
-    for pair in pairs {
-        let kv: Vec<&str> = pair.splitn(2, key_value_seperator).collect();
-        if kv.len() < 2 {
-            continue;
+    /// checks if the key is in the Record without a value.
+    pub fn is_flag(&self, key: &str) -> bool {
+        self.iter().any(|pair| pair.0 == key && pair.1.is_none())
+    }
+
+    /// parses the last value of the key. It returns `Ok(None)` if the key is missing and an
+    /// error if the value can not be parsed.
+    ///
+    /// # Example
+    /// ```
+    /// use sqlib::map::Record;
+    ///
+    /// let record = Record::parse("clid=1 cid=abc");
+    ///
+    /// assert_eq!(record.parse_value::<i64>("clid").unwrap(), Some(1));
+    /// assert_eq!(record.parse_value::<i64>("client_type").unwrap(), None);
+    /// assert!(record.parse_value::<i64>("cid").is_err());
+    /// ```
+    pub fn parse_value<T>(&self, key: &str) -> Result<Option<T>>
+    where
+        T: FromStr,
+    {
+        match self.get(key) {
+            None => Ok(None),
+            Some(v) => v
+                .parse()
+                .map(Some)
+                .map_err(|_| Error::invalid_value(key, v)),
         }
-        let key = kv[0].to_string();
-        let value = kv[1].to_string();
-        map.insert(key, value);
     }
 
-    map
+    /// returns the number of pairs.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// checks if the Record has no pairs.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// iterates over all pairs in their original order.
+    pub fn iter(&self) -> slice::Iter<'_, (String, Option<String>)> {
+        self.0.iter()
+    }
+
+    /// creates a StringMap like `to_map`. Keys without a value are left out and the last value
+    /// of duplicate keys is kept.
+    pub fn to_map(&self) -> StringMap {
+        self.iter()
+            .filter_map(|(k, v)| v.as_ref().map(|v| (k.clone(), v.clone())))
+            .collect()
+    }
+}
+
+impl FromIterator<(String, Option<String>)> for Record {
+    fn from_iter<I>(iter: I) -> Record
+    where
+        I: IntoIterator<Item = (String, Option<String>)>,
+    {
+        Record(iter.into_iter().collect())
+    }
+}
+
+impl FromStr for Record {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(Record::parse(s))
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut first = true;
+        for (key, value) in self.iter() {
+            if !first {
+                write!(f, " ")?;
+            }
+            first = false;
+            match value {
+                Some(value) => write!(f, "{}={}", key, value)?,
+                None => write!(f, "{}", key)?,
+            }
+        }
+        Ok(())
+    }
 }
 
 /// This function gets the value to the key from the map, then parses it and mutates the given
@@ -68,9 +352,161 @@ where
     T: FromStr,
 {
     if let Some(v) = map.get(key) {
-        let r: Result<T, _> = v.parse();
+        let r: ::std::result::Result<T, _> = v.parse();
         if let Ok(v) = r {
             *value = v;
         }
     }
 }
+
+/// The strict version of `update_from_map`: it returns an error if the value can not be parsed.
+/// A missing key is not an error and leaves the pointer unchanged.
+///
+/// # Example
+/// ```
+/// use sqlib::map::{to_map, try_update_from_map};
+///
+/// let map = to_map("key1=2 key2=abc");
+/// let mut integer = 1i32;
+///
+/// try_update_from_map(&map, "key1", &mut integer).unwrap();
+/// assert_eq!(integer, 2);
+///
+/// assert!(try_update_from_map(&map, "key2", &mut integer).is_err());
+/// assert_eq!(integer, 2);
+/// ```
+pub fn try_update_from_map<T>(map: &StringMap, key: &str, value: &mut T) -> Result<()>
+where
+    T: FromStr,
+{
+    if let Some(v) = map.get(key) {
+        *value = v.parse().map_err(|_| Error::invalid_value(key, v))?;
+    }
+    Ok(())
+}
+
+/// A RecordRef is a Record, that borrows its keys and values from the answer.
+///
+/// Parsing it copies nothing and a value is only unescaped, when it is read with `get`, so it is
+/// cheaper than a StringMap for large answers, e.g. a `clientlist` with many options.
+///
+/// # Example
+/// ```
+/// use sqlib::map::RecordRef;
+/// use std::borrow::Cow;
+///
+/// let answer = "clid=1 client_nickname=John\\sDoe|clid=2 client_nickname=test".to_string();
+/// let records = RecordRef::parse_list(&answer);
+///
+/// assert_eq!(records[0].get("client_nickname").unwrap(), "John Doe");
+/// assert_eq!(records[0].get_raw("client_nickname"), Some("John\\sDoe"));
+/// assert_eq!(records[1].get("client_nickname"), Some(Cow::Borrowed("test")));
+/// assert_eq!(records[1].parse_value::<i64>("clid").unwrap(), Some(2));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordRef<'a>(Vec<(&'a str, Option<&'a str>)>);
+
+impl<'a> RecordRef<'a> {
+    /// parses a single record. It never fails, every token is kept.
+    pub fn parse(string: &'a str) -> RecordRef<'a> {
+        RecordRef(pairs(string).collect())
+    }
+
+    /// parses all records of an answer, that are seperated by `|`.
+    pub fn parse_list(string: &'a str) -> Vec<RecordRef<'a>> {
+        records(string).map(RecordRef::parse).collect()
+    }
+
+    /// returns the last escaped value of the key. A key without a value has the value "".
+    pub fn get_raw(&self, key: &str) -> Option<&'a str> {
+        self.iter()
+            .rev()
+            .find(|pair| pair.0 == key)
+            .map(|pair| pair.1.unwrap_or(""))
+    }
+
+    /// returns the last unescaped value of the key. It is only copied, if it contains an escape
+    /// sequence.
+    pub fn get(&self, key: &str) -> Option<Cow<'a, str>> {
+        self.get_raw(key).map(unescape_cow)
+    }
+
+    /// returns all unescaped values of the key in their original order.
+    pub fn get_all(&self, key: &str) -> Vec<Cow<'a, str>> {
+        self.iter()
+            .filter(|pair| pair.0 == key)
+            .map(|pair| unescape_cow(pair.1.unwrap_or("")))
+            .collect()
+    }
+
+    /// checks if the key is in the RecordRef, with or without a value.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.iter().any(|pair| pair.0 == key)
+    }
+
+    /// checks if the key is in the RecordRef without a value.
+    pub fn is_flag(&self, key: &str) -> bool {
+        self.iter().any(|pair| pair.0 == key && pair.1.is_none())
+    }
+
+    /// parses the last unescaped value of the key, like `Record::parse_value`.
+    pub fn parse_value<T>(&self, key: &str) -> Result<Option<T>>
+    where
+        T: FromStr,
+    {
+        match self.get(key) {
+            None => Ok(None),
+            Some(v) => v
+                .parse()
+                .map(Some)
+                .map_err(|_| Error::invalid_value(key, &v)),
+        }
+    }
+
+    /// returns the number of pairs.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// checks if the RecordRef has no pairs.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// iterates over all escaped pairs in their original order.
+    pub fn iter(&self) -> slice::Iter<'_, (&'a str, Option<&'a str>)> {
+        self.0.iter()
+    }
+
+    /// copies the pairs into a Record.
+    pub fn to_record(&self) -> Record {
+        self.iter()
+            .map(|&(k, v)| (k.to_string(), v.map(|v| v.to_string())))
+            .collect()
+    }
+
+    /// creates a StringMap with the escaped values, like `Record::to_map`. Keys without a value
+    /// are left out.
+    pub fn to_map(&self) -> StringMap {
+        self.iter()
+            .filter_map(|&(k, v)| v.map(|v| (k.to_string(), v.to_string())))
+            .collect()
+    }
+}
+
+impl<'a> fmt::Display for RecordRef<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut first = true;
+        for &(key, value) in self.iter() {
+            if !first {
+                write!(f, " ")?;
+            }
+            first = false;
+            match value {
+                Some(value) => write!(f, "{}={}", key, value)?,
+                None => write!(f, "{}", key)?,
+            }
+        }
+        Ok(())
+    }
+}
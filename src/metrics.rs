@@ -0,0 +1,351 @@
+//! The metrics module contains the Metrics struct, that counts the commands, errors,
+//! notifications and flood backoffs of connections and exports them in the Prometheus text
+//! format.
+//!
+//! A Metrics is shared by an `Arc` between any number of connections. The text of `render` can
+//! be served directly on a `/metrics` endpoint.
+//!
+//! # Example
+//! ```
+//! use sqlib::connection::Connection;
+//! use sqlib::metrics::Metrics;
+//! use sqlib::testing::{MockServer, Reply};
+//! use std::sync::Arc;
+//!
+//! let server = MockServer::start().unwrap();
+//! server.on("whoami", Reply::ok("virtualserver_id=1"));
+//!
+//! let metrics = Arc::new(Metrics::new());
+//! let mut conn = Connection::new(&server.addr()).unwrap();
+//! conn.set_metrics(metrics.clone());
+//!
+//! conn.send_command(&"whoami").unwrap();
+//! conn.send_command(&"unknown").unwrap_err();
+//!
+//! assert_eq!(metrics.commands(), 2);
+//! assert_eq!(metrics.errors(256), 1);
+//!
+//! let text = metrics.render();
+//! assert!(text.contains("sqlib_commands_total 2\n"));
+//! assert!(text.contains("sqlib_command_errors_total{id=\"256\"} 1\n"));
+//! assert!(text.contains("sqlib_command_duration_seconds_count 2\n"));
+//! ```
+
+use error::{Error, Result};
+use map::{update_from_map, StringMap};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// the upper bounds in seconds of the buckets of the command latency histogram
+pub const LATENCY_BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+/// ServerGauges contains the state of a monitored virtual server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ServerGauges {
+    /// the clients online, without the Server Query clients
+    pub clients_online: i64,
+    /// the number of channels
+    pub channels: i64,
+    /// the uptime in seconds
+    pub uptime: i64,
+}
+
+/// Metrics contains the counters and the latency histogram of the connections, that share it,
+/// and the gauges of the monitored virtual servers.
+///
+/// A command, that is retried after flood errors, is counted once with the answer and the
+/// latency of its last attempt; the retries are counted as flood backoffs. Commands of
+/// `send_batch` are counted, but are not part of the latency histogram, because their answers
+/// overlap.
+///
+/// The reconnects are the sessions of a ConnectionPool, that failed the health check and were
+/// replaced by a new connection.
+///
+/// # Example
+/// ```
+/// use sqlib::connection::Connection;
+/// use sqlib::metrics::Metrics;
+/// use sqlib::ratelimit::RateLimit;
+/// use sqlib::testing::{MockServer, Reply};
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// let server = MockServer::start().unwrap();
+/// server.on("whoami", Reply::ok("virtualserver_id=1"));
+/// server.once("whoami", Reply::error(524, "client is flooding"));
+///
+/// let metrics = Arc::new(Metrics::new());
+/// let mut conn = Connection::new(&server.addr()).unwrap();
+/// conn.set_rate_limit(RateLimit::new(10, Duration::from_millis(10)));
+/// conn.set_metrics(metrics.clone());
+/// conn.send_command(&"whoami").unwrap();
+///
+/// assert_eq!(server.received(), vec!["whoami", "whoami"]);
+/// assert_eq!(metrics.commands(), 1);
+/// assert_eq!(metrics.errors(524), 0);
+/// assert_eq!(metrics.flood_backoffs(), 1);
+/// ```
+#[derive(Debug, Default)]
+pub struct Metrics {
+    commands: AtomicU64,
+    errors: Mutex<BTreeMap<u32, u64>>,
+    // the observations of every bucket, the last one is above all bounds
+    latency: [AtomicU64; 13],
+    latency_sum_micros: AtomicU64,
+    reconnects: AtomicU64,
+    notifications: AtomicU64,
+    flood_backoffs: AtomicU64,
+    servers: Mutex<BTreeMap<u64, ServerGauges>>,
+}
+
+impl Metrics {
+    /// creates a new Metrics, where all counters are 0.
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    /// counts a sent command and the error id of its answer.
+    pub fn command<T>(&self, result: &Result<T>) {
+        self.commands.fetch_add(1, Ordering::Relaxed);
+        if let Err(Error::SQ(ref e)) = *result {
+            if let Ok(mut errors) = self.errors.lock() {
+                *errors.entry(e.id()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// adds the time between a command and its answer to the latency histogram.
+    pub fn latency(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|&bound| seconds <= bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.latency[bucket].fetch_add(1, Ordering::Relaxed);
+        self.latency_sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// counts a pooled session, that failed the health check and was replaced.
+    pub fn reconnect(&self) {
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// counts a received notification.
+    pub fn notification(&self) {
+        self.notifications.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// counts a backoff after a command was refused because of flooding.
+    pub fn flood_backoff(&self) {
+        self.flood_backoffs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// sets the gauges of the virtual server with the id `sid`.
+    pub fn set_server(&self, sid: u64, gauges: ServerGauges) {
+        if let Ok(mut servers) = self.servers.lock() {
+            servers.insert(sid, gauges);
+        }
+    }
+
+    /// sets the gauges of a virtual server from the answer of `serverinfo`.
+    ///
+    /// # Example
+    /// ```
+    /// use sqlib::map::to_map;
+    /// use sqlib::metrics::Metrics;
+    ///
+    /// let metrics = Metrics::new();
+    /// let info = to_map(
+    ///     "virtualserver_id=1 virtualserver_clientsonline=5 virtualserver_queryclientsonline=1 \
+    ///      virtualserver_channelsonline=12 virtualserver_uptime=3600",
+    /// );
+    /// metrics.observe_server(&info);
+    ///
+    /// let gauges = metrics.server(1).unwrap();
+    /// assert_eq!(gauges.clients_online, 4);
+    /// assert_eq!(gauges.channels, 12);
+    /// assert!(metrics.render().contains("sqlib_server_uptime_seconds{sid=\"1\"} 3600\n"));
+    /// ```
+    pub fn observe_server(&self, info: &StringMap) {
+        let mut sid = 0u64;
+        let mut clients = 0i64;
+        let mut query_clients = 0i64;
+        let mut gauges = ServerGauges::default();
+        update_from_map(info, "virtualserver_id", &mut sid);
+        update_from_map(info, "virtualserver_clientsonline", &mut clients);
+        update_from_map(info, "virtualserver_queryclientsonline", &mut query_clients);
+        update_from_map(info, "virtualserver_channelsonline", &mut gauges.channels);
+        update_from_map(info, "virtualserver_uptime", &mut gauges.uptime);
+        gauges.clients_online = clients - query_clients;
+        self.set_server(sid, gauges);
+    }
+
+    /// removes the gauges of a virtual server, that is not monitored anymore.
+    pub fn remove_server(&self, sid: u64) {
+        if let Ok(mut servers) = self.servers.lock() {
+            servers.remove(&sid);
+        }
+    }
+
+    /// returns the gauges of the virtual server with the id `sid`.
+    pub fn server(&self, sid: u64) -> Option<ServerGauges> {
+        self.servers
+            .lock()
+            .ok()
+            .and_then(|servers| servers.get(&sid).cloned())
+    }
+
+    /// returns the number of sent commands. Retries after flood errors are not counted.
+    pub fn commands(&self) -> u64 {
+        self.commands.load(Ordering::Relaxed)
+    }
+
+    /// returns the number of answers with the error id `id`.
+    pub fn errors(&self, id: u32) -> u64 {
+        self.errors
+            .lock()
+            .ok()
+            .and_then(|errors| errors.get(&id).cloned())
+            .unwrap_or(0)
+    }
+
+    /// returns the number of pooled sessions, that failed the health check and were replaced.
+    pub fn reconnects(&self) -> u64 {
+        self.reconnects.load(Ordering::Relaxed)
+    }
+
+    /// returns the number of received notifications.
+    pub fn notifications(&self) -> u64 {
+        self.notifications.load(Ordering::Relaxed)
+    }
+
+    /// returns the number of backoffs after flood errors.
+    pub fn flood_backoffs(&self) -> u64 {
+        self.flood_backoffs.load(Ordering::Relaxed)
+    }
+
+    /// creates the Prometheus text format of all metrics.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        counter(
+            &mut out,
+            "sqlib_commands_total",
+            "The number of sent commands.",
+            self.commands(),
+        );
+
+        header(
+            &mut out,
+            "sqlib_command_errors_total",
+            "The number of answers with an error id.",
+            "counter",
+        );
+        if let Ok(errors) = self.errors.lock() {
+            for (id, count) in errors.iter() {
+                let _ = writeln!(out, "sqlib_command_errors_total{{id=\"{}\"}} {}", id, count);
+            }
+        }
+
+        header(
+            &mut out,
+            "sqlib_command_duration_seconds",
+            "The time between a command and its answer.",
+            "histogram",
+        );
+        let mut cumulative = 0;
+        for (i, bound) in LATENCY_BUCKETS.iter().enumerate() {
+            cumulative += self.latency[i].load(Ordering::Relaxed);
+            let _ = writeln!(
+                out,
+                "sqlib_command_duration_seconds_bucket{{le=\"{}\"}} {}",
+                bound, cumulative
+            );
+        }
+        cumulative += self.latency[LATENCY_BUCKETS.len()].load(Ordering::Relaxed);
+        let sum = self.latency_sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(
+            out,
+            "sqlib_command_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+            cumulative
+        );
+        let _ = writeln!(out, "sqlib_command_duration_seconds_sum {}", sum);
+        let _ = writeln!(out, "sqlib_command_duration_seconds_count {}", cumulative);
+
+        counter(
+            &mut out,
+            "sqlib_reconnects_total",
+            "The number of pooled sessions, that failed the health check and were replaced.",
+            self.reconnects(),
+        );
+        counter(
+            &mut out,
+            "sqlib_notifications_total",
+            "The number of received notifications.",
+            self.notifications(),
+        );
+        counter(
+            &mut out,
+            "sqlib_flood_backoffs_total",
+            "The number of backoffs after flood errors.",
+            self.flood_backoffs(),
+        );
+
+        let servers = match self.servers.lock() {
+            Ok(servers) => servers.clone(),
+            Err(_) => BTreeMap::new(),
+        };
+        gauge(
+            &mut out,
+            "sqlib_server_clients_online",
+            "The clients online of a virtual server without Server Query clients.",
+            &servers,
+            |g| g.clients_online,
+        );
+        gauge(
+            &mut out,
+            "sqlib_server_channels",
+            "The number of channels of a virtual server.",
+            &servers,
+            |g| g.channels,
+        );
+        gauge(
+            &mut out,
+            "sqlib_server_uptime_seconds",
+            "The uptime of a virtual server.",
+            &servers,
+            |g| g.uptime,
+        );
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    header(out, name, help, "counter");
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn gauge<F>(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    servers: &BTreeMap<u64, ServerGauges>,
+    value: F,
+) where
+    F: Fn(&ServerGauges) -> i64,
+{
+    header(out, name, help, "gauge");
+    for (sid, server) in servers.iter() {
+        let _ = writeln!(out, "{}{{sid=\"{}\"}} {}", name, sid, value(server));
+    }
+}
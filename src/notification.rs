@@ -0,0 +1,100 @@
+//! The notification module contains the Notification struct, a representation of an event the
+//! server sends after a `servernotifyregister`.
+//!
+//! # Example
+//! ```
+//! use sqlib::notification::Notification;
+//!
+//! let line = "notifyclientmoved ctid=2 reasonid=0 clid=5|clid=6";
+//! let notification = Notification::parse(line).unwrap();
+//!
+//! assert_eq!(notification.name(), "notifyclientmoved");
+//! assert_eq!(notification.maps().len(), 2);
+//! assert_eq!(notification.maps()[1].get("ctid").unwrap(), "2");
+//! ```
+
+use error;
+use map::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// Every notification of the server starts with this prefix.
+pub const NOTIFICATION_PREFIX: &str = "notify";
+
+/// Notification contains an event of the server, e.g. `notifycliententerview`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Notification {
+    name: String,
+    args: String,
+    maps: Vec<StringMap>,
+}
+
+impl Notification {
+    /// checks if a line of the server is a notification.
+    pub fn is_notification(line: &str) -> bool {
+        line.starts_with(NOTIFICATION_PREFIX)
+    }
+
+    /// tries to parse a line of the server into a Notification.
+    pub fn parse(line: &str) -> Option<Notification> {
+        let line = line.trim_matches(|c: char| c.is_ascii_whitespace());
+        if !Notification::is_notification(line) {
+            return None;
+        }
+        let mut parts = line.splitn(2, |c: char| c.is_ascii_whitespace());
+        let name = parts.next().unwrap_or("").to_string();
+        let args = parts.next().unwrap_or("").to_string();
+
+        // All records after the first only contain the keys, that differ from the first one.
+        let mut maps: Vec<StringMap> = Vec::new();
+        for record in args.split('|') {
+            let mut map = match maps.first() {
+                Some(first) => first.clone(),
+                None => StringMap::new(),
+            };
+            map.extend(to_map(record));
+            maps.push(map);
+        }
+
+        Some(Notification { name, args, maps })
+    }
+
+    /// returns the name of the notification, e.g. `notifycliententerview`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// returns the raw arguments of the notification.
+    pub fn args(&self) -> &str {
+        &self.args
+    }
+
+    /// returns a map for every record of the notification.
+    ///
+    /// Records after the first one contain the keys of the first record, too.
+    pub fn maps(&self) -> &[StringMap] {
+        &self.maps
+    }
+
+    /// returns the value of the key in the first record.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.maps
+            .first()
+            .and_then(|map| map.get(key))
+            .map(|v| v.as_str())
+    }
+}
+
+impl FromStr for Notification {
+    type Err = error::Error;
+    fn from_str(s: &str) -> error::Result<Self> {
+        Notification::parse(s).ok_or_else(|| error::Error::from("not a notification"))
+    }
+}
+
+impl fmt::Display for Notification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.args)
+    }
+}
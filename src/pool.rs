@@ -0,0 +1,466 @@
+//! The pool module contains the ConnectionPool struct, that keeps Server Query connections open
+//! and hands them out already logged in and selected onto a virtual server.
+//!
+//! `use` changes the state of the whole session, so a Connection can only serve one virtual
+//! server at a time. The pool keeps separate sessions for every address, virtual server and
+//! login.
+//!
+//! # Example
+//! ```no_run
+//! use sqlib::pool::ConnectionPool;
+//! use std::time::Duration;
+//!
+//! let pool = ConnectionPool::new()
+//!     .max_per_host(4)
+//!     .idle_timeout(Duration::from_secs(60));
+//!
+//! let mut conn = pool.get("127.0.0.1:10011", 1, "serveradmin", "password").unwrap();
+//! println!("{}", conn.clientlist().unwrap());
+//! // the session is returned to the pool, when it is dropped
+//! ```
+
+use connection::Connection;
+use error;
+use error::Error;
+use map::*;
+use metrics::Metrics;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::BuildHasher;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// the default number of sessions per host, the default of the server's
+/// `serverinstance_serverquery_max_connections_per_ip` is 10
+pub const DEFAULT_MAX_PER_HOST: usize = 10;
+/// the default time after that an idle session is closed
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+/// the default time `get` waits for a free session
+pub const DEFAULT_CHECKOUT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// PoolKey identifies the sessions, that can be used for each other.
+///
+/// Sessions are only used for each other, if they were opened with the same password, so a
+/// `get` with a wrong password never gets a session, that is already logged in.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PoolKey {
+    pub addr: String,
+    pub sid: u64,
+    pub login: String,
+    // a hash of the password, that is keyed with the random keys of the pool
+    password: u64,
+}
+
+impl fmt::Display for PoolKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}@{}/{}", self.login, self.addr, self.sid)
+    }
+}
+
+#[derive(Debug)]
+struct Idle {
+    conn: Connection,
+    since: Instant,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    idle: HashMap<PoolKey, Vec<Idle>>,
+    // the number of idle and checked out sessions of every host
+    open: HashMap<String, usize>,
+}
+
+impl State {
+    fn open(&self, addr: &str) -> usize {
+        self.open.get(addr).cloned().unwrap_or(0)
+    }
+
+    fn release(&mut self, addr: &str) {
+        if let Some(n) = self.open.get_mut(addr) {
+            *n = n.saturating_sub(1);
+        }
+    }
+
+    // removes the idle sessions, that are older than the timeout.
+    fn take_expired(&mut self, timeout: Duration) -> Vec<Connection> {
+        let mut expired = Vec::new();
+        for (key, sessions) in &mut self.idle {
+            let (old, young) = mem::take(sessions)
+                .into_iter()
+                .partition(|idle: &Idle| idle.since.elapsed() >= timeout);
+            *sessions = young;
+            for idle in old {
+                if let Some(n) = self.open.get_mut(&key.addr) {
+                    *n = n.saturating_sub(1);
+                }
+                expired.push(idle.conn);
+            }
+        }
+        self.idle.retain(|_, sessions| !sessions.is_empty());
+        expired
+    }
+
+    // removes the oldest idle session of the host, that belongs to another key.
+    fn take_other(&mut self, key: &PoolKey) -> Option<Connection> {
+        let other = self
+            .idle
+            .iter()
+            .filter(|&(k, sessions)| k.addr == key.addr && k != key && !sessions.is_empty())
+            .min_by_key(|&(_, sessions)| sessions[0].since)
+            .map(|(k, _)| k.clone())?;
+        let (idle, now_empty) = {
+            let sessions = self.idle.get_mut(&other)?;
+            (sessions.remove(0), sessions.is_empty())
+        };
+        if now_empty {
+            self.idle.remove(&other);
+        }
+        self.release(&key.addr);
+        Some(idle.conn)
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    max_per_host: usize,
+    idle_timeout: Duration,
+    checkout_timeout: Duration,
+    metrics: Option<Arc<Metrics>>,
+    hasher: RandomState,
+    state: Mutex<State>,
+    returned: Condvar,
+}
+
+/// ConnectionPool keeps Server Query sessions keyed by address, virtual server id and login.
+///
+/// Sessions are checked with `whoami` before they are handed out again. The number of open
+/// sessions per host is limited, because the server bans hosts with too many connections.
+/// The pool can be cloned cheaply and shared between threads.
+///
+/// The settings have to be set before the pool is cloned or used. A setter on a pool, that has
+/// clones, returns a new empty pool with the settings, that does not share any idle or checked
+/// out sessions with the clones.
+#[derive(Debug, Clone)]
+pub struct ConnectionPool {
+    inner: Arc<Inner>,
+}
+
+impl Default for ConnectionPool {
+    fn default() -> ConnectionPool {
+        ConnectionPool::new()
+    }
+}
+
+impl ConnectionPool {
+    /// creates a new empty ConnectionPool with the default limits.
+    pub fn new() -> ConnectionPool {
+        ConnectionPool {
+            inner: Arc::new(Inner {
+                max_per_host: DEFAULT_MAX_PER_HOST,
+                idle_timeout: DEFAULT_IDLE_TIMEOUT,
+                checkout_timeout: DEFAULT_CHECKOUT_TIMEOUT,
+                metrics: None,
+                hasher: RandomState::new(),
+                state: Mutex::new(State::default()),
+                returned: Condvar::new(),
+            }),
+        }
+    }
+
+    // changes the settings of the pool. A pool, that was already cloned, is replaced by a new
+    // empty pool with the same settings.
+    fn configure<F>(self, f: F) -> ConnectionPool
+    where
+        F: FnOnce(&mut Inner),
+    {
+        let mut inner = match Arc::try_unwrap(self.inner) {
+            Ok(inner) => inner,
+            Err(shared) => Inner {
+                max_per_host: shared.max_per_host,
+                idle_timeout: shared.idle_timeout,
+                checkout_timeout: shared.checkout_timeout,
+                metrics: shared.metrics.clone(),
+                hasher: RandomState::new(),
+                state: Mutex::new(State::default()),
+                returned: Condvar::new(),
+            },
+        };
+        f(&mut inner);
+        ConnectionPool {
+            inner: Arc::new(inner),
+        }
+    }
+
+    /// sets the maximum number of open sessions per host. It is at least 1.
+    pub fn max_per_host(self, max: usize) -> ConnectionPool {
+        self.configure(|inner| inner.max_per_host = max.max(1))
+    }
+
+    /// sets the time after that an idle session is closed.
+    pub fn idle_timeout(self, timeout: Duration) -> ConnectionPool {
+        self.configure(|inner| inner.idle_timeout = timeout)
+    }
+
+    /// sets the time `get` waits for a free session, if the host has no free slot.
+    pub fn checkout_timeout(self, timeout: Duration) -> ConnectionPool {
+        self.configure(|inner| inner.checkout_timeout = timeout)
+    }
+
+    /// sets the Metrics of all sessions of the pool. Sessions, that are replaced because they
+    /// failed the health check, are counted as reconnects.
+    pub fn metrics(self, metrics: Arc<Metrics>) -> ConnectionPool {
+        self.configure(|inner| inner.metrics = Some(metrics))
+    }
+
+    /// returns a session, that is logged in with the login and selected onto the virtual server
+    /// with the id `sid`.
+    ///
+    /// An idle session of the same key is reused, if it passes the health check. Otherwise a
+    /// new session is opened. If the host has reached its limit, an idle session of another key
+    /// is closed, or `get` waits until a session is returned.
+    pub fn get(
+        &self,
+        addr: &str,
+        sid: u64,
+        login: &str,
+        password: &str,
+    ) -> error::Result<PooledConnection> {
+        let key = PoolKey {
+            addr: addr.to_string(),
+            sid,
+            login: login.to_string(),
+            password: self.inner.hasher.hash_one(password),
+        };
+        let deadline = Instant::now() + self.inner.checkout_timeout;
+
+        loop {
+            let (reused, evicted) = self.checkout(&key, deadline)?;
+            close_all(evicted);
+
+            let conn = match reused {
+                Some(mut conn) => {
+                    if is_healthy(&mut conn, sid) {
+                        conn
+                    } else {
+                        self.discard_slot(&key.addr);
+                        let _ = conn.quit();
+                        if let Some(ref metrics) = self.inner.metrics {
+                            metrics.reconnect();
+                        }
+                        continue;
+                    }
+                }
+                None => match open(&key, password, self.inner.metrics.as_ref()) {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        self.discard_slot(&key.addr);
+                        return Err(e);
+                    }
+                },
+            };
+
+            return Ok(PooledConnection {
+                conn: Some(conn),
+                key,
+                pool: self.clone(),
+            });
+        }
+    }
+
+    // takes an idle session of the key or reserves a slot for a new one. It also returns the
+    // sessions, that have to be closed.
+    fn checkout(
+        &self,
+        key: &PoolKey,
+        deadline: Instant,
+    ) -> error::Result<(Option<Connection>, Vec<Connection>)> {
+        let mut state = self.inner.state.lock()?;
+        let mut evicted = state.take_expired(self.inner.idle_timeout);
+        loop {
+            if let Some(idle) = state.idle.get_mut(key).and_then(|sessions| sessions.pop()) {
+                return Ok((Some(idle.conn), evicted));
+            }
+            if state.open(&key.addr) >= self.inner.max_per_host {
+                if let Some(conn) = state.take_other(key) {
+                    evicted.push(conn);
+                }
+            }
+            if state.open(&key.addr) < self.inner.max_per_host {
+                *state.open.entry(key.addr.clone()).or_insert(0) += 1;
+                return Ok((None, evicted));
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                drop(state);
+                close_all(evicted);
+                return Err(Error::from(format!(
+                    "no free session for {} within the checkout timeout",
+                    key.addr
+                )));
+            }
+            state = self.inner.returned.wait_timeout(state, deadline - now)?.0;
+        }
+    }
+
+    fn discard_slot(&self, addr: &str) {
+        if let Ok(mut state) = self.inner.state.lock() {
+            state.release(addr);
+        }
+        self.inner.returned.notify_all();
+    }
+
+    fn put_back(&self, key: PoolKey, conn: Connection) {
+        if let Ok(mut state) = self.inner.state.lock() {
+            state.idle.entry(key).or_default().push(Idle {
+                conn,
+                since: Instant::now(),
+            });
+        }
+        self.inner.returned.notify_all();
+    }
+
+    /// closes all idle sessions, that were not used within the idle timeout, and returns their
+    /// number.
+    pub fn evict_idle(&self) -> usize {
+        let expired = match self.inner.state.lock() {
+            Ok(mut state) => state.take_expired(self.inner.idle_timeout),
+            Err(_) => return 0,
+        };
+        let n = expired.len();
+        close_all(expired);
+        self.inner.returned.notify_all();
+        n
+    }
+
+    /// closes all idle sessions. Checked out sessions are not affected.
+    pub fn clear(&self) {
+        let idle = match self.inner.state.lock() {
+            Ok(mut state) => {
+                let idle: Vec<(PoolKey, Vec<Idle>)> = state.idle.drain().collect();
+                for (key, sessions) in &idle {
+                    for _ in sessions {
+                        state.release(&key.addr);
+                    }
+                }
+                idle
+            }
+            Err(_) => return,
+        };
+        close_all(
+            idle.into_iter()
+                .flat_map(|(_, sessions)| sessions)
+                .map(|idle| idle.conn),
+        );
+        self.inner.returned.notify_all();
+    }
+
+    /// returns the number of idle sessions.
+    pub fn idle_count(&self) -> usize {
+        self.inner
+            .state
+            .lock()
+            .map(|state| state.idle.values().map(|sessions| sessions.len()).sum())
+            .unwrap_or(0)
+    }
+
+    /// returns the number of idle and checked out sessions of a host.
+    pub fn open_count(&self, addr: &str) -> usize {
+        self.inner
+            .state
+            .lock()
+            .map(|state| state.open(addr))
+            .unwrap_or(0)
+    }
+}
+
+// opens a new session for the key.
+fn open(
+    key: &PoolKey,
+    password: &str,
+    metrics: Option<&Arc<Metrics>>,
+) -> error::Result<Connection> {
+    let mut conn = Connection::new(&key.addr)?;
+    if let Some(metrics) = metrics {
+        conn.set_metrics(metrics.clone());
+    }
+    conn.login(&key.login, password)?;
+    conn.use_server_id(key.sid)?;
+    Ok(conn)
+}
+
+// checks the session with `whoami` and selects the virtual server again, if it was changed.
+fn is_healthy(conn: &mut Connection, sid: u64) -> bool {
+    let whoami: StringMap = match conn.query(&"whoami") {
+        Ok(whoami) => whoami,
+        Err(_) => return false,
+    };
+    let mut current = 0u64;
+    update_from_map(&whoami, "virtualserver_id", &mut current);
+    current == sid || conn.use_server_id(sid).is_ok()
+}
+
+fn close_all<I>(conns: I)
+where
+    I: IntoIterator<Item = Connection>,
+{
+    for mut conn in conns {
+        let _ = conn.quit();
+    }
+}
+
+/// PooledConnection is a session checked out of a ConnectionPool. It dereferences to a
+/// Connection and is returned to the pool, when it is dropped.
+///
+/// Do not call `use`, `login` or `quit` on it directly; use `discard` for sessions, that should
+/// not be reused.
+#[derive(Debug)]
+pub struct PooledConnection {
+    conn: Option<Connection>,
+    key: PoolKey,
+    pool: ConnectionPool,
+}
+
+impl PooledConnection {
+    /// returns the key of the session.
+    pub fn key(&self) -> &PoolKey {
+        &self.key
+    }
+
+    /// closes the session instead of returning it to the pool.
+    pub fn discard(mut self) {
+        if let Some(mut conn) = self.conn.take() {
+            let _ = conn.quit();
+            self.pool.discard_slot(&self.key.addr);
+        }
+    }
+}
+
+impl Deref for PooledConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn
+            .as_ref()
+            .expect("the session was already returned")
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn
+            .as_mut()
+            .expect("the session was already returned")
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool.put_back(self.key.clone(), conn);
+        }
+    }
+}
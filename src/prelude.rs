@@ -1,9 +1,27 @@
 //! The prelude exports all important structs and functions of sqlib.
 
+pub use apikey::{ApiKey, ApiKeyScope};
 pub use channel::{Channel, ChannelList};
-pub use client::{Client, ClientList};
+pub use client::{Client, ClientList, ClientListOptions};
+pub use clientquery::ClientQueryConnection;
 pub use command::Command;
 pub use connection::Connection;
-pub use map::{to_map, update_from_map, StringMap};
+pub use diff::{diff, diff_clients, Change};
+pub use map::{
+    to_map, try_update_from_map, update_from_map, FromStringMap, Record, RecordRef, StringMap,
+    ToParams,
+};
+pub use metrics::Metrics;
+pub use notification::Notification;
+pub use pool::{ConnectionPool, PooledConnection};
+pub use querylogin::{QueryLogin, QueryLoginFilter};
+pub use ratelimit::RateLimit;
+pub use response::FromResponse;
+pub use server::{CreatedServer, ServerProperties};
+pub use shared::SharedConnection;
+pub use state::ServerState;
+pub use textmessage::TextMessageTarget;
+pub use transport::Transport;
+pub use webquery::WebQueryClient;
 
 pub use error::{Error, Result, SQError};
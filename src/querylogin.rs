@@ -0,0 +1,98 @@
+//! The querylogin module contains the QueryLogin struct, a representation of a Server Query
+//! login.
+//!
+//! # Example
+//! ```
+//! use sqlib::querylogin::QueryLogin;
+//! use sqlib::response::FromResponse;
+//!
+//! let reply = "cldbid=2 sid=1 client_login_name=bot1|cldbid=3 sid=1 client_login_name=bot2";
+//! let logins = Vec::<QueryLogin>::from_response(reply).unwrap();
+//!
+//! assert_eq!(logins.len(), 2);
+//! assert_eq!(logins[1].client_login_name, "bot2");
+//! ```
+
+use error;
+use escaping::escape;
+use map::*;
+use std::str::FromStr;
+
+/// QueryLogin contains a Server Query login of a client.
+#[derive(Debug, Clone, Default, PartialEq, Eq, FromStringMap)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct QueryLogin {
+    /// client database id
+    pub cldbid: i64,
+    /// virtual server id, 0 for a login of the server instance
+    pub sid: u64,
+    /// login name
+    #[sqlib(escape)]
+    pub client_login_name: String,
+}
+
+impl FromStr for QueryLogin {
+    type Err = error::Error;
+    fn from_str(s: &str) -> error::Result<Self> {
+        let map = to_map(s);
+        Ok(QueryLogin::from_map(&map))
+    }
+}
+
+/// QueryLoginFilter contains the optional pattern and paging of a `queryloginlist` command.
+///
+/// # Example
+/// ```
+/// use sqlib::querylogin::QueryLoginFilter;
+///
+/// let filter = QueryLoginFilter::new().pattern("bot%").start(10).duration(10);
+///
+/// assert_eq!(filter.params(), "pattern=bot% start=10 duration=10");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryLoginFilter {
+    pattern: Option<String>,
+    start: Option<u32>,
+    duration: Option<u32>,
+}
+
+impl QueryLoginFilter {
+    /// creates a filter, that matches all logins.
+    pub fn new() -> QueryLoginFilter {
+        QueryLoginFilter::default()
+    }
+
+    /// only lists logins, whose name matches the SQL LIKE pattern (e.g. `bot%`).
+    pub fn pattern(mut self, pattern: &str) -> QueryLoginFilter {
+        self.pattern = Some(pattern.to_string());
+        self
+    }
+
+    /// skips the first `start` logins.
+    pub fn start(mut self, start: u32) -> QueryLoginFilter {
+        self.start = Some(start);
+        self
+    }
+
+    /// lists at most `duration` logins.
+    pub fn duration(mut self, duration: u32) -> QueryLoginFilter {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// creates the escaped parameters of the filter.
+    pub fn params(&self) -> String {
+        let mut params = Vec::new();
+        if let Some(ref pattern) = self.pattern {
+            params.push(format!("pattern={}", escape(pattern)));
+        }
+        if let Some(start) = self.start {
+            params.push(format!("start={}", start));
+        }
+        if let Some(duration) = self.duration {
+            params.push(format!("duration={}", duration));
+        }
+        params.join(" ")
+    }
+}
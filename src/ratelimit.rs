@@ -0,0 +1,154 @@
+//! The ratelimit module contains the RateLimit struct, a sliding window, that keeps a Connection
+//! below the flood limits of the server.
+//!
+//! Clients, that are not on the query whitelist of the server, are banned if they send more
+//! than `serverinstance_serverquery_flood_commands` commands within
+//! `serverinstance_serverquery_flood_time` seconds.
+//!
+//! # Example
+//! ```no_run
+//! use sqlib::connection::Connection;
+//! use sqlib::ratelimit::RateLimit;
+//! use std::time::Duration;
+//!
+//! let mut conn = Connection::new("127.0.0.1:10011").unwrap();
+//!
+//! // the server allows 20 commands per second
+//! conn.set_rate_limit(RateLimit::new(20, Duration::from_secs(1)));
+//!
+//! // the host is on the whitelist
+//! conn.set_rate_limit(RateLimit::disabled());
+//! ```
+
+use std::collections::VecDeque;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// the default of `serverinstance_serverquery_flood_commands`
+pub const DEFAULT_FLOOD_COMMANDS: u32 = 10;
+/// the default of `serverinstance_serverquery_flood_time`
+pub const DEFAULT_FLOOD_TIME: Duration = Duration::from_secs(3);
+/// the default number of retries after a flood error
+pub const DEFAULT_RETRIES: u32 = 3;
+
+/// RateLimit is a sliding window, that allows at most `commands` commands within any period
+/// of `per`, like the flood protection of the server counts them.
+///
+/// # Example
+/// ```
+/// use sqlib::ratelimit::RateLimit;
+/// use std::time::{Duration, Instant};
+///
+/// let mut limit = RateLimit::new(2, Duration::from_secs(60));
+///
+/// assert_eq!(limit.wait_time(), Duration::from_secs(0));
+/// limit.acquire();
+/// limit.acquire();
+/// assert!(limit.wait_time() > Duration::from_secs(59));
+///
+/// // no period of `per` contains more than `commands` commands
+/// let per = Duration::from_millis(100);
+/// let mut limit = RateLimit::new(3, per);
+/// let mut sent = Vec::new();
+/// for _ in 0..10 {
+///     limit.acquire();
+///     sent.push(Instant::now());
+/// }
+/// for window in sent.windows(4) {
+///     assert!(window[3] - window[0] >= per);
+/// }
+///
+/// assert!(!RateLimit::disabled().is_enabled());
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    commands: u32,
+    per: Duration,
+    retries: u32,
+    enabled: bool,
+    // the times of the last `commands` commands
+    sent: VecDeque<Instant>,
+}
+
+impl Default for RateLimit {
+    /// creates a RateLimit with the default flood limits of the server.
+    fn default() -> RateLimit {
+        RateLimit::new(DEFAULT_FLOOD_COMMANDS, DEFAULT_FLOOD_TIME)
+    }
+}
+
+impl RateLimit {
+    /// creates a new RateLimit, that allows `commands` commands per `per`.
+    pub fn new(commands: u32, per: Duration) -> RateLimit {
+        let commands = commands.max(1);
+        RateLimit {
+            commands,
+            per,
+            retries: DEFAULT_RETRIES,
+            enabled: true,
+            sent: VecDeque::with_capacity(commands as usize),
+        }
+    }
+
+    /// creates a RateLimit, that never waits, for hosts on the query whitelist.
+    ///
+    /// Flood errors are still retried.
+    pub fn disabled() -> RateLimit {
+        RateLimit {
+            enabled: false,
+            ..RateLimit::default()
+        }
+    }
+
+    /// sets how often a command is retried after the server returned a flood error.
+    pub fn retries(mut self, retries: u32) -> RateLimit {
+        self.retries = retries;
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn get_retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// returns how long the next command has to wait.
+    pub fn wait_time(&mut self) -> Duration {
+        if !self.enabled || self.sent.len() < self.commands as usize {
+            return Duration::from_secs(0);
+        }
+        // the oldest command has to leave the window
+        match self.sent.front() {
+            Some(&oldest) => (oldest + self.per).saturating_duration_since(Instant::now()),
+            None => Duration::from_secs(0),
+        }
+    }
+
+    /// waits until a command may be sent and counts it.
+    pub fn acquire(&mut self) {
+        let wait = self.wait_time();
+        if wait > Duration::from_secs(0) {
+            thread::sleep(wait);
+        }
+        if self.enabled {
+            if self.sent.len() >= self.commands as usize {
+                self.sent.pop_front();
+            }
+            self.sent.push_back(Instant::now());
+        }
+    }
+
+    /// waits a whole flood period after the server returned a flood error. The window is
+    /// filled afterwards, because the server counts more commands than the window did, so the
+    /// next commands are spread over the next period.
+    pub fn backoff(&mut self) {
+        thread::sleep(self.per);
+        let now = Instant::now();
+        let step = self.per / self.commands;
+        self.sent = (0..self.commands)
+            .filter_map(|i| now.checked_sub(self.per - step * (i + 1)))
+            .collect();
+    }
+}
@@ -0,0 +1,466 @@
+//! The recording module contains transports, that record a session to a transcript and replay
+//! it later, e.g. to reproduce a parsing bug of a production server offline.
+//!
+//! A transcript is a text file with one line per line of the protocol:
+//!
+//! ```text
+//! 0 < TS3
+//! 0 < Welcome to the TeamSpeak 3 ServerQuery interface, ...
+//! 12 > clientlist
+//! 13 < clid=1 cid=1 client_database_id=1 client_nickname=test client_type=0
+//! 13 < error id=0 msg=ok
+//! 5012 < notifyclientleftview cfid=1 ctid=0 reasonid=8 clid=1
+//! ```
+//!
+//! The number is the time in milliseconds since the start of the recording, `>` marks the
+//! commands of the client and `<` the lines of the server, including notifications.
+//!
+//! Secrets are never written to a transcript: passwords, API keys and tokens in the commands
+//! and lines are replaced by `***` (see `trace::redact` and `trace::redact_reply`). A replay
+//! compares the redacted commands, so it accepts a session with any password.
+//!
+//! # Example
+//! ```
+//! use sqlib::connection::Connection;
+//! use sqlib::recording::{RecordingTransport, ReplayTransport, Transcript};
+//! use sqlib::testing::{MockServer, Reply};
+//! use std::net::TcpStream;
+//!
+//! let server = MockServer::start().unwrap();
+//! server.on("clientlist", Reply::ok("clid=1 cid=1 client_database_id=1 client_nickname=odd\\p|clid=2"));
+//!
+//! // record a session
+//! let path = std::env::temp_dir().join(format!("sqlib-transcript-{}.txt", server.addr().replace(':', "-")));
+//! let stream = TcpStream::connect(server.addr()).unwrap();
+//! let transport = RecordingTransport::create(stream, &path).unwrap();
+//! let mut conn = Connection::with_transport(&server.addr(), transport).unwrap();
+//! let recorded = conn.clientlist().unwrap();
+//! drop(conn);
+//!
+//! // replay it without the server
+//! let transcript = Transcript::from_file(&path).unwrap();
+//! let mut conn = Connection::with_transport("replay", ReplayTransport::new(transcript)).unwrap();
+//! assert_eq!(conn.clientlist().unwrap(), recorded);
+//!
+//! // a different command is an error
+//! assert!(conn.channellist().is_err());
+//! # std::fs::remove_file(&path).unwrap();
+//! ```
+
+use error;
+use error::Error;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use trace::{redact, redact_reply};
+use transport::Transport;
+
+/// Direction is the sender of a line of a transcript.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// a command of the client, `>`
+    Sent,
+    /// a line of the server, `<`
+    Received,
+}
+
+impl Direction {
+    fn symbol(self) -> &'static str {
+        match self {
+            Direction::Sent => ">",
+            Direction::Received => "<",
+        }
+    }
+}
+
+/// Entry is a single line of a transcript.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// the time since the start of the recording
+    pub elapsed: Duration,
+    pub direction: Direction,
+    /// the line without the line break
+    pub line: String,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.elapsed.as_millis(),
+            self.direction.symbol(),
+            self.line
+        )
+    }
+}
+
+impl FromStr for Entry {
+    type Err = Error;
+    fn from_str(s: &str) -> error::Result<Self> {
+        let mut parts = s.splitn(3, ' ');
+        let elapsed = parts
+            .next()
+            .and_then(|ms| ms.parse::<u64>().ok())
+            .map(Duration::from_millis)
+            .ok_or_else(|| Error::from(format!("invalid transcript line {:?}", s)))?;
+        let direction = match parts.next() {
+            Some(">") => Direction::Sent,
+            Some("<") => Direction::Received,
+            _ => return Err(Error::from(format!("invalid transcript line {:?}", s))),
+        };
+        let line = parts.next().unwrap_or("").to_string();
+        Ok(Entry {
+            elapsed,
+            direction,
+            line,
+        })
+    }
+}
+
+/// Transcript contains all entries of a recorded session.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Transcript {
+    entries: Vec<Entry>,
+}
+
+impl Transcript {
+    /// reads a transcript file. Empty lines and lines starting with `#` are skipped.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> error::Result<Transcript> {
+        let mut s = String::new();
+        File::open(path)?.read_to_string(&mut s)?;
+        s.parse()
+    }
+
+    /// returns the entries in their order.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// returns the lines of the server, that are notifications.
+    pub fn notifications(&self) -> Vec<&Entry> {
+        self.entries
+            .iter()
+            .filter(|e| e.direction == Direction::Received && e.line.starts_with("notify"))
+            .collect()
+    }
+}
+
+impl From<Vec<Entry>> for Transcript {
+    fn from(entries: Vec<Entry>) -> Transcript {
+        Transcript { entries }
+    }
+}
+
+impl FromStr for Transcript {
+    type Err = Error;
+    fn from_str(s: &str) -> error::Result<Self> {
+        let entries = s
+            .lines()
+            .map(|line| line.trim_end_matches('\r'))
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::parse)
+            .collect::<error::Result<Vec<Entry>>>()?;
+        Ok(Transcript { entries })
+    }
+}
+
+impl fmt::Display for Transcript {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+// splits a byte stream into lines and calls the function for every complete line.
+#[derive(Debug, Default)]
+struct LineSplitter {
+    partial: Vec<u8>,
+}
+
+impl LineSplitter {
+    fn push<F>(&mut self, bytes: &[u8], mut f: F) -> io::Result<()>
+    where
+        F: FnMut(&str) -> io::Result<()>,
+    {
+        for &b in bytes {
+            if b == b'\n' {
+                let line = String::from_utf8_lossy(&self.partial).into_owned();
+                self.partial.clear();
+                // the server ends its lines with `\n\r`
+                f(line.trim_matches('\r'))?;
+            } else {
+                self.partial.push(b);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// RecordingTransport wraps another Transport and writes every line, that is sent or received,
+/// to a transcript. The secrets in the lines are redacted.
+///
+/// # Example
+/// ```
+/// use sqlib::connection::Connection;
+/// use sqlib::recording::{RecordingTransport, ReplayTransport, Transcript};
+/// use sqlib::testing::{MockServer, Reply};
+/// use std::net::TcpStream;
+///
+/// let server = MockServer::start().unwrap();
+/// server.on("queryloginadd", Reply::ok("client_login_name=bot client_login_password=s3cr3t"));
+///
+/// let path = std::env::temp_dir().join(format!("sqlib-redacted-{}.txt", server.addr().replace(':', "-")));
+/// let stream = TcpStream::connect(server.addr()).unwrap();
+/// let mut conn = Connection::with_transport(&server.addr(), RecordingTransport::create(stream, &path).unwrap()).unwrap();
+/// conn.login("serveradmin", "password").unwrap();
+/// conn.send_command(&"queryloginadd client_login_name=bot").unwrap();
+/// drop(conn);
+///
+/// let transcript = std::fs::read_to_string(&path).unwrap();
+/// assert!(transcript.contains("> login serveradmin ***"));
+/// assert!(transcript.contains("< client_login_name=bot client_login_password=***"));
+/// assert!(!transcript.contains("s3cr3t"));
+///
+/// // the replay accepts the login with the real password
+/// let replay = ReplayTransport::new(Transcript::from_file(&path).unwrap());
+/// let mut conn = Connection::with_transport("replay", replay).unwrap();
+/// conn.login("serveradmin", "password").unwrap();
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct RecordingTransport<T> {
+    inner: T,
+    transcript: Box<dyn Write + Send>,
+    start: Instant,
+    sent: LineSplitter,
+    received: LineSplitter,
+}
+
+impl<T: Transport> RecordingTransport<T> {
+    /// wraps the Transport and writes the transcript to the writer.
+    pub fn new<W>(inner: T, transcript: W) -> RecordingTransport<T>
+    where
+        W: Write + Send + 'static,
+    {
+        RecordingTransport {
+            inner,
+            transcript: Box::new(transcript),
+            start: Instant::now(),
+            sent: LineSplitter::default(),
+            received: LineSplitter::default(),
+        }
+    }
+
+    /// wraps the Transport and writes the transcript to a new file at the path.
+    pub fn create<P: AsRef<Path>>(inner: T, path: P) -> error::Result<RecordingTransport<T>> {
+        let file = File::create(path)?;
+        Ok(RecordingTransport::new(inner, file))
+    }
+
+    /// returns the wrapped Transport.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+fn record(
+    transcript: &mut dyn Write,
+    start: Instant,
+    direction: Direction,
+    line: &str,
+) -> io::Result<()> {
+    let line = match direction {
+        Direction::Sent => redact(line),
+        Direction::Received => redact_reply(line),
+    };
+    let entry = Entry {
+        elapsed: start.elapsed(),
+        direction,
+        line,
+    };
+    writeln!(transcript, "{}", entry)?;
+    transcript.flush()
+}
+
+impl<T: Transport> Read for RecordingTransport<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        let (transcript, start) = (&mut self.transcript, self.start);
+        self.received.push(&buf[..n], |line| {
+            record(&mut **transcript, start, Direction::Received, line)
+        })?;
+        Ok(n)
+    }
+}
+
+impl<T: Transport> Write for RecordingTransport<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        let (transcript, start) = (&mut self.transcript, self.start);
+        self.sent.push(&buf[..n], |line| {
+            record(&mut **transcript, start, Direction::Sent, line)
+        })?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<T: Transport> Transport for RecordingTransport<T> {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.inner.set_read_timeout(timeout)
+    }
+
+    fn shutdown(&mut self) -> io::Result<()> {
+        self.transcript.flush()?;
+        self.inner.shutdown()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RecordingTransport<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RecordingTransport")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+/// ReplayTransport plays the lines of the server of a Transcript back, without a server.
+///
+/// The lines, that the server sent before the next command of the transcript, are available
+/// for reading. Every command, that is written, has to match the next command of the
+/// transcript, unless the transport is lenient. The replay does not wait for the recorded
+/// times.
+#[derive(Debug)]
+pub struct ReplayTransport {
+    entries: VecDeque<Entry>,
+    // the bytes, that can be read until the next command
+    readable: VecDeque<u8>,
+    sent: LineSplitter,
+    timeout: Option<Duration>,
+    lenient: bool,
+}
+
+impl ReplayTransport {
+    /// creates a ReplayTransport for the transcript.
+    pub fn new(transcript: Transcript) -> ReplayTransport {
+        let mut transport = ReplayTransport {
+            entries: transcript.entries.into(),
+            readable: VecDeque::new(),
+            sent: LineSplitter::default(),
+            timeout: None,
+            lenient: false,
+        };
+        transport.release();
+        transport
+    }
+
+    /// reads a transcript file and creates a ReplayTransport for it.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> error::Result<ReplayTransport> {
+        Ok(ReplayTransport::new(Transcript::from_file(path)?))
+    }
+
+    /// does not check the written commands; every command releases the lines up to the next
+    /// recorded command.
+    pub fn lenient(mut self) -> ReplayTransport {
+        self.lenient = true;
+        self
+    }
+
+    /// returns the entries, that were not replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.entries.len()
+    }
+
+    // makes the lines of the server readable, that were received before the next command.
+    fn release(&mut self) {
+        while let Some(entry) = self.entries.front() {
+            if entry.direction == Direction::Sent {
+                break;
+            }
+            self.readable.extend(entry.line.bytes());
+            self.readable.extend(b"\n\r");
+            self.entries.pop_front();
+        }
+    }
+
+    // checks a written command against the transcript.
+    fn command(&mut self, line: &str) -> io::Result<()> {
+        match self.entries.pop_front() {
+            // the transcript only contains the redacted command
+            Some(ref entry) if self.lenient || redact(&entry.line) == redact(line) => {}
+            Some(entry) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("expected the command {:?}, got {:?}", entry.line, line),
+                ))
+            }
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("the transcript has no command {:?}", line),
+                ))
+            }
+        }
+        self.release();
+        Ok(())
+    }
+}
+
+impl Read for ReplayTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.readable.is_empty() {
+            // the recorded server sent nothing more before the next command
+            if self.timeout.is_some() && !self.entries.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    "the transcript has no more lines before the next command",
+                ));
+            }
+            return Ok(0);
+        }
+        let n = buf.len().min(self.readable.len());
+        for (dst, src) in buf.iter_mut().zip(self.readable.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for ReplayTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut commands = Vec::new();
+        self.sent.push(buf, |line| {
+            commands.push(line.to_string());
+            Ok(())
+        })?;
+        for command in commands {
+            self.command(&command)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+//! response contains the FromResponse trait, that creates typed values from the answer of a
+//! Server Query command.
+//!
+//! There are implementations for a single record (all types, that implement `FromStringMap`),
+//! a list of records (`Vec<T>`, `ClientList`, `ChannelList`) and the empty reply (`()`).
+//!
+//! # Example
+//! ```
+//! use sqlib::map::StringMap;
+//! use sqlib::response::FromResponse;
+//! use sqlib::client::{Client, ClientList};
+//!
+//! let response = "clid=1 cid=1 client_nickname=test1|clid=2 cid=1 client_nickname=test2";
+//!
+//! let clients = ClientList::from_response(response).unwrap();
+//! let maps = Vec::<StringMap>::from_response(response).unwrap();
+//! let first = Client::from_response(response).unwrap();
+//!
+//! assert_eq!(clients.len(), 2);
+//! assert_eq!(maps[1].get("client_nickname").unwrap(), "test2");
+//! assert_eq!(first.client_nickname, "test1");
+//! ```
+
+use error::{Error, Result, SQError};
+use map::*;
+use notification::Notification;
+
+/// A trait for types, that can be created from the answer of a Server Query command.
+pub trait FromResponse: Sized {
+    /// creates Self from the answer of a command.
+    fn from_response(response: &str) -> Result<Self>;
+
+    /// creates Self from the answer of a command, but returns an error if a value can not be
+    /// parsed, instead of skipping it.
+    ///
+    /// # Example
+    /// ```
+    /// use sqlib::client::ClientList;
+    /// use sqlib::response::FromResponse;
+    ///
+    /// let response = "clid=1 cid=1|clid=abc cid=1";
+    ///
+    /// assert_eq!(ClientList::from_response(response).unwrap().len(), 2);
+    /// assert!(ClientList::from_response_strict(response).unwrap_err().is_parse());
+    /// ```
+    fn from_response_strict(response: &str) -> Result<Self> {
+        Self::from_response(response)
+    }
+}
+
+/// splits a response into its records. Empty records are skipped.
+///
+/// # Example
+/// ```
+/// use sqlib::response::records;
+///
+/// assert_eq!(records("a=1|a=2").count(), 2);
+/// assert_eq!(records("").count(), 0);
+/// ```
+pub fn records<'a>(response: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    response.split('|').filter(|record| {
+        !record
+            .trim_matches(|c: char| c.is_ascii_whitespace())
+            .is_empty()
+    })
+}
+
+/// A single record. If the response contains more than one record, the first one is used.
+impl<T> FromResponse for T
+where
+    T: FromStringMap,
+{
+    fn from_response(response: &str) -> Result<Self> {
+        let record = records(response)
+            .next()
+            .ok_or_else(|| Error::from("empty response"))?;
+        Ok(T::from_map(&to_map(record)))
+    }
+
+    fn from_response_strict(response: &str) -> Result<Self> {
+        let record = records(response)
+            .next()
+            .ok_or_else(|| Error::from("empty response"))?;
+        T::try_from_map(&to_map(record))
+    }
+}
+
+/// A list of records.
+impl<T> FromResponse for Vec<T>
+where
+    T: FromStringMap,
+{
+    fn from_response(response: &str) -> Result<Self> {
+        Ok(records(response)
+            .map(|record| T::from_map(&to_map(record)))
+            .collect())
+    }
+
+    fn from_response_strict(response: &str) -> Result<Self> {
+        records(response)
+            .map(|record| T::try_from_map(&to_map(record)))
+            .collect()
+    }
+}
+
+/// The raw answer.
+impl FromResponse for String {
+    fn from_response(response: &str) -> Result<Self> {
+        Ok(response.to_string())
+    }
+}
+
+/// The empty reply. The content of the response is ignored.
+impl FromResponse for () {
+    fn from_response(_: &str) -> Result<Self> {
+        Ok(())
+    }
+}
+
+/// decodes a line of the server. The line starts without the control characters of the last
+/// line ending ("\n\r").
+///
+/// # Example
+/// ```
+/// use sqlib::response::decode_line;
+///
+/// assert_eq!(decode_line(b"\rerror id=0 msg=ok\n"), "error id=0 msg=ok\n");
+/// ```
+pub fn decode_line(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_start_matches(char::is_control)
+        .to_string()
+}
+
+/// Line is a single line of the server, sorted by a ReplyCollector.
+#[derive(Debug)]
+pub enum Line {
+    /// an event of the server
+    Notification(Notification),
+    /// the complete answer of a command or its error
+    Reply(Result<String>),
+    /// a part of an answer, that is not complete yet
+    Partial,
+}
+
+/// ReplyCollector collects the lines of the server into the answers of the commands.
+///
+/// # Example
+/// ```
+/// use sqlib::response::{Line, ReplyCollector};
+///
+/// let mut collector = ReplyCollector::new();
+///
+/// match collector.push_line("clid=1") {
+///     Line::Partial => {}
+///     _ => panic!("expected a partial answer"),
+/// }
+/// match collector.push_line("notifyclientleftview clid=2") {
+///     Line::Notification(n) => assert_eq!(n.name(), "notifyclientleftview"),
+///     _ => panic!("expected a notification"),
+/// }
+/// match collector.push_line("error id=0 msg=ok") {
+///     Line::Reply(answer) => assert_eq!(answer.unwrap(), "clid=1"),
+///     _ => panic!("expected an answer"),
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct ReplyCollector {
+    result: String,
+}
+
+impl ReplyCollector {
+    /// creates a ReplyCollector without a partial answer.
+    pub fn new() -> ReplyCollector {
+        ReplyCollector::default()
+    }
+
+    /// sorts the next line of the server.
+    pub fn push_line(&mut self, line: &str) -> Line {
+        if let Some(notification) = Notification::parse(line) {
+            return Line::Notification(notification);
+        }
+        match SQError::parse_is_ok(line) {
+            Ok(false) => {
+                self.result += line; // + "\n";
+                Line::Partial
+            }
+            Ok(true) => Line::Reply(Ok(self.result.split_off(0))),
+            Err(e) => {
+                self.result.clear();
+                Line::Reply(Err(e))
+            }
+        }
+    }
+}
@@ -0,0 +1,590 @@
+//! The ser module contains a serde Serializer for the Server Query format.
+//!
+//! It is the counterpart of the Deserializer in the de module: a struct or a map is written as a
+//! record of escaped `key=value` pairs and a sequence of them as records, that are seperated by
+//! `|`. `None` values are left out, a unit value is written as a key without a value and a `bool`
+//! as `1` or `0`.
+//!
+//! # Example
+//! ```
+//! #[macro_use]
+//! extern crate serde;
+//! extern crate sqlib;
+//!
+//! #[derive(Serialize)]
+//! struct Ban {
+//!     ip: Option<String>,
+//!     name: String,
+//!     time: u64,
+//!     banreason: String,
+//! }
+//!
+//! # fn main() {
+//! let ban = Ban {
+//!     ip: None,
+//!     name: "John Doe".to_string(),
+//!     time: 600,
+//!     banreason: "too loud".to_string(),
+//! };
+//!
+//! let params = sqlib::to_string(&ban).unwrap();
+//! assert_eq!(params, "name=John\\sDoe time=600 banreason=too\\sloud");
+//! # }
+//! ```
+
+use error::{Error, Result};
+use escaping::escape;
+use serde::ser::{self, Impossible, Serialize};
+use std::fmt::Display;
+
+/// Serializer writes records in the Server Query format.
+#[derive(Debug, Default)]
+pub struct Serializer {
+    output: String,
+    // the number of records of the current sequence, `None` outside of a sequence
+    records: Option<usize>,
+}
+
+impl Serializer {
+    /// creates a new Serializer.
+    pub fn new() -> Serializer {
+        Serializer::default()
+    }
+
+    /// returns the written records.
+    pub fn into_inner(self) -> String {
+        self.output
+    }
+}
+
+/// serializes a struct or a map as a record or a sequence of them as records, e.g. for the
+/// parameters of a command.
+pub fn to_string<T>(value: &T) -> Result<String>
+where
+    T: Serialize + ?Sized,
+{
+    let mut serializer = Serializer::new();
+    value.serialize(&mut serializer)?;
+    Ok(serializer.into_inner())
+}
+
+fn unsupported(what: &str) -> Error {
+    Error::Other(format!("{} can not be serialized as a record", what))
+}
+
+impl<'a> ser::Serializer for &'a mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = RecordSerializer<'a>;
+    type SerializeStruct = RecordSerializer<'a>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn serialize_bool(self, _v: bool) -> Result<()> {
+        Err(unsupported("a bool"))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<()> {
+        Err(unsupported("a number"))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<()> {
+        Err(unsupported("a number"))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<()> {
+        Err(unsupported("a number"))
+    }
+
+    fn serialize_char(self, _v: char) -> Result<()> {
+        Err(unsupported("a char"))
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<()> {
+        Err(unsupported("a string"))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<()> {
+        Err(unsupported("bytes"))
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        Err(unsupported("an enum"))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()> {
+        Err(unsupported("an enum"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self> {
+        if self.records.is_some() {
+            return Err(unsupported("a nested sequence"));
+        }
+        self.records = Some(0);
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Self> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(unsupported("an enum"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<RecordSerializer<'a>> {
+        Ok(RecordSerializer {
+            output: &mut self.output,
+            empty: true,
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<RecordSerializer<'a>> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(unsupported("an enum"))
+    }
+}
+
+impl ser::SerializeSeq for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let records = self.records.unwrap_or(0);
+        if records > 0 {
+            self.output.push('|');
+        }
+        self.records = Some(records + 1);
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        self.records = None;
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<()> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<()> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+/// RecordSerializer writes the pairs of a single record.
+#[derive(Debug)]
+pub struct RecordSerializer<'a> {
+    output: &'a mut String,
+    empty: bool,
+    key: Option<String>,
+}
+
+impl<'a> RecordSerializer<'a> {
+    fn write_pair(&mut self, key: &str, value: Value) {
+        let value = match value {
+            Value::Missing => return,
+            Value::Flag => None,
+            Value::Text(text) => Some(text),
+        };
+        if !self.empty {
+            self.output.push(' ');
+        }
+        self.empty = false;
+        self.output.push_str(key);
+        if let Some(value) = value {
+            self.output.push('=');
+            self.output.push_str(&value);
+        }
+    }
+}
+
+impl<'a> ser::SerializeStruct for RecordSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        let value = value.serialize(ValueSerializer)?;
+        self.write_pair(key, value);
+        Ok(())
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeMap for RecordSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        match key.serialize(ValueSerializer)? {
+            Value::Text(key) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(unsupported("a key without a name")),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| Error::from("a value without a key"))?;
+        let value = value.serialize(ValueSerializer)?;
+        self.write_pair(&key, value);
+        Ok(())
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// the serialized value of a single key
+enum Value {
+    // the pair is left out
+    Missing,
+    // the key is written without a value
+    Flag,
+    // the escaped value
+    Text(String),
+}
+
+// serializes a single value of a record
+struct ValueSerializer;
+
+impl ValueSerializer {
+    fn text<T: Display>(v: T) -> Result<Value> {
+        Ok(Value::Text(escape(&v.to_string())))
+    }
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    type SerializeSeq = ListSerializer;
+    type SerializeTuple = ListSerializer;
+    type SerializeTupleStruct = ListSerializer;
+    type SerializeTupleVariant = Impossible<Value, Error>;
+    type SerializeMap = Impossible<Value, Error>;
+    type SerializeStruct = Impossible<Value, Error>;
+    type SerializeStructVariant = Impossible<Value, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Value> {
+        ValueSerializer::text(if v { 1 } else { 0 })
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value> {
+        ValueSerializer::text(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value> {
+        ValueSerializer::text(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value> {
+        ValueSerializer::text(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value> {
+        ValueSerializer::text(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Value> {
+        ValueSerializer::text(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value> {
+        ValueSerializer::text(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value> {
+        ValueSerializer::text(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value> {
+        ValueSerializer::text(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value> {
+        ValueSerializer::text(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Value> {
+        ValueSerializer::text(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value> {
+        ValueSerializer::text(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value> {
+        ValueSerializer::text(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value> {
+        ValueSerializer::text(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value> {
+        Ok(Value::Text(escape(v)))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value> {
+        match ::std::str::from_utf8(v) {
+            Ok(v) => self.serialize_str(v),
+            Err(_) => Err(unsupported("a value, that is not UTF-8,")),
+        }
+    }
+
+    fn serialize_none(self) -> Result<Value> {
+        Ok(Value::Missing)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value> {
+        Ok(Value::Flag)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value> {
+        Ok(Value::Flag)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Value> {
+        Err(unsupported("an enum with data"))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<ListSerializer> {
+        Ok(ListSerializer {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<ListSerializer> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<ListSerializer> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(unsupported("an enum with data"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(unsupported("a nested map"))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(unsupported("a nested struct"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(unsupported("an enum with data"))
+    }
+}
+
+// serializes a list inside a value, that is seperated by `,`
+struct ListSerializer {
+    items: Vec<String>,
+}
+
+impl ser::SerializeSeq for ListSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        match value.serialize(ValueSerializer)? {
+            // a `,` would split the item, when it is read again
+            Value::Text(ref text) if text.contains(',') => {
+                return Err(unsupported("a list item with a ','"))
+            }
+            Value::Text(text) => self.items.push(text),
+            Value::Flag => self.items.push(String::new()),
+            Value::Missing => {}
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(Value::Text(self.items.join(",")))
+    }
+}
+
+impl ser::SerializeTuple for ListSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for ListSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value> {
+        ser::SerializeSeq::end(self)
+    }
+}
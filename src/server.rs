@@ -0,0 +1,236 @@
+//! The server module contains types around the virtual server lifecycle.
+//!
+//! # Example
+//! ```
+//! use sqlib::server::ServerProperties;
+//!
+//! let properties = ServerProperties::new()
+//!     .name("Tournament 1")
+//!     .max_clients(32);
+//!
+//! assert_eq!(
+//!     properties.params(),
+//!     "virtualserver_name=Tournament\\s1 virtualserver_maxclients=32"
+//! );
+//! ```
+
+use error;
+use escaping::escape;
+use map::*;
+use std::str::FromStr;
+
+/// ServerProperties is a builder for the properties of a virtual server, used by
+/// `servercreate` and `serveredit`.
+///
+/// Only the properties that were set are send to the server.
+///
+/// # Example
+/// ```
+/// use sqlib::server::ServerProperties;
+///
+/// let properties = ServerProperties::new()
+///     .name("test")
+///     .welcome_message("hello world")
+///     .port(9988);
+///
+/// assert_eq!(properties.get_name(), Some("test"));
+/// assert_eq!(
+///     properties.params(),
+///     "virtualserver_name=test virtualserver_welcomemessage=hello\\sworld \
+///      virtualserver_port=9988"
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ServerProperties {
+    name: Option<String>,
+    welcome_message: Option<String>,
+    max_clients: Option<u32>,
+    reserved_slots: Option<u32>,
+    port: Option<u16>,
+    password: Option<String>,
+    host_message: Option<String>,
+    host_message_mode: Option<u8>,
+    host_banner_url: Option<String>,
+    host_banner_gfx_url: Option<String>,
+    host_button_url: Option<String>,
+    host_button_tooltip: Option<String>,
+    autostart: Option<bool>,
+}
+
+impl ServerProperties {
+    /// creates an empty ServerProperties builder.
+    pub fn new() -> ServerProperties {
+        ServerProperties::default()
+    }
+
+    /// sets the name of the virtual server.
+    pub fn name(mut self, name: &str) -> ServerProperties {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// sets the welcome message, that is shown to connecting clients.
+    pub fn welcome_message(mut self, message: &str) -> ServerProperties {
+        self.welcome_message = Some(message.to_string());
+        self
+    }
+
+    /// sets the number of client slots.
+    pub fn max_clients(mut self, max_clients: u32) -> ServerProperties {
+        self.max_clients = Some(max_clients);
+        self
+    }
+
+    /// sets the number of reserved client slots.
+    pub fn reserved_slots(mut self, slots: u32) -> ServerProperties {
+        self.reserved_slots = Some(slots);
+        self
+    }
+
+    /// sets the UDP voice port of the virtual server.
+    pub fn port(mut self, port: u16) -> ServerProperties {
+        self.port = Some(port);
+        self
+    }
+
+    /// sets the password of the virtual server.
+    pub fn password(mut self, password: &str) -> ServerProperties {
+        self.password = Some(password.to_string());
+        self
+    }
+
+    /// sets the host message.
+    pub fn host_message(mut self, message: &str) -> ServerProperties {
+        self.host_message = Some(message.to_string());
+        self
+    }
+
+    /// sets the host message mode: 0 is none, 1 is log, 2 is modal and 3 is modal quit.
+    pub fn host_message_mode(mut self, mode: u8) -> ServerProperties {
+        self.host_message_mode = Some(mode);
+        self
+    }
+
+    /// sets the URL the host banner links to.
+    pub fn host_banner_url(mut self, url: &str) -> ServerProperties {
+        self.host_banner_url = Some(url.to_string());
+        self
+    }
+
+    /// sets the URL of the host banner image.
+    pub fn host_banner_gfx_url(mut self, url: &str) -> ServerProperties {
+        self.host_banner_gfx_url = Some(url.to_string());
+        self
+    }
+
+    /// sets the URL the host button links to.
+    pub fn host_button_url(mut self, url: &str) -> ServerProperties {
+        self.host_button_url = Some(url.to_string());
+        self
+    }
+
+    /// sets the tooltip of the host button.
+    pub fn host_button_tooltip(mut self, tooltip: &str) -> ServerProperties {
+        self.host_button_tooltip = Some(tooltip.to_string());
+        self
+    }
+
+    /// sets if the virtual server is started together with the server instance.
+    pub fn autostart(mut self, autostart: bool) -> ServerProperties {
+        self.autostart = Some(autostart);
+        self
+    }
+
+    /// returns the name, if it was set.
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// checks if no property was set.
+    pub fn is_empty(&self) -> bool {
+        *self == ServerProperties::default()
+    }
+
+    /// creates the escaped `key=value` parameters of all set properties.
+    pub fn params(&self) -> String {
+        let mut params = Vec::new();
+        {
+            let mut push = |key: &str, value: Option<String>| {
+                if let Some(value) = value {
+                    params.push(format!("{}={}", key, escape(&value)));
+                }
+            };
+            push("virtualserver_name", self.name.clone());
+            push("virtualserver_welcomemessage", self.welcome_message.clone());
+            push(
+                "virtualserver_maxclients",
+                self.max_clients.map(|v| v.to_string()),
+            );
+            push(
+                "virtualserver_reserved_slots",
+                self.reserved_slots.map(|v| v.to_string()),
+            );
+            push("virtualserver_port", self.port.map(|v| v.to_string()));
+            push("virtualserver_password", self.password.clone());
+            push("virtualserver_hostmessage", self.host_message.clone());
+            push(
+                "virtualserver_hostmessage_mode",
+                self.host_message_mode.map(|v| v.to_string()),
+            );
+            push("virtualserver_hostbanner_url", self.host_banner_url.clone());
+            push(
+                "virtualserver_hostbanner_gfx_url",
+                self.host_banner_gfx_url.clone(),
+            );
+            push("virtualserver_hostbutton_url", self.host_button_url.clone());
+            push(
+                "virtualserver_hostbutton_tooltip",
+                self.host_button_tooltip.clone(),
+            );
+            push(
+                "virtualserver_autostart",
+                self.autostart.map(|v| (v as u8).to_string()),
+            );
+        }
+        params.join(" ")
+    }
+}
+
+/// CreatedServer contains the answer of a `servercreate` command.
+///
+/// # Example
+/// ```
+/// use sqlib::server::CreatedServer;
+///
+/// let created: CreatedServer = "sid=2 virtualserver_port=9988 token=abc\\/def"
+///     .parse()
+///     .unwrap();
+///
+/// assert_eq!(created.sid, 2);
+/// assert_eq!(created.virtualserver_port, 9988);
+/// assert_eq!(created.token, "abc/def");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, FromStringMap)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct CreatedServer {
+    /// virtual server id
+    pub sid: u64,
+    /// UDP voice port of the new virtual server
+    pub virtualserver_port: u16,
+    /// the initial privilege key (server admin token)
+    #[sqlib(escape)]
+    pub token: String,
+}
+
+impl FromStr for CreatedServer {
+    type Err = error::Error;
+    fn from_str(s: &str) -> error::Result<Self> {
+        let map = to_map(s);
+        if !map.contains_key("sid") {
+            return Err(error::Error::from("servercreate returned no sid"));
+        }
+        Ok(CreatedServer::from_map(&map))
+    }
+}
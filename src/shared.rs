@@ -0,0 +1,428 @@
+//! The shared module contains the SharedConnection struct, a Server Query connection, that can
+//! be used by many threads at the same time.
+//!
+//! # Example
+//! ```no_run
+//! use sqlib::shared::SharedConnection;
+//! use std::thread;
+//!
+//! let conn = SharedConnection::new("127.0.0.1:10011").unwrap();
+//! conn.login("serveradmin", "password").unwrap();
+//! conn.use_server_id(1).unwrap();
+//!
+//! let notifications = conn.subscribe();
+//! conn.register_notifications("server", None).unwrap();
+//!
+//! let poller = conn.clone();
+//! thread::spawn(move || loop {
+//!     println!("{}", poller.clientlist().unwrap());
+//!     thread::sleep(std::time::Duration::from_secs(5));
+//! });
+//!
+//! for notification in notifications {
+//!     println!("{}", notification);
+//! }
+//! ```
+
+use channel::ChannelList;
+use client::ClientList;
+use command::Command;
+use error;
+use error::Error;
+use notification::Notification;
+use ratelimit::RateLimit;
+use response::{decode_line, FromResponse, Line, ReplyCollector};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+use transport::Transport;
+
+/// the read timeout of the reader thread, if the transport has no separate writer, like the TLS
+/// and SSH transports. A command waits at most this long for the reader to release the
+/// transport.
+pub const READ_TIMEOUT: Duration = Duration::from_millis(20);
+
+// the state, that is shared between the callers and the reader thread
+#[derive(Debug, Default)]
+struct State {
+    // the callers, that wait for an answer, in the order of their commands
+    pending: VecDeque<mpsc::Sender<error::Result<String>>>,
+    subscribers: Vec<mpsc::Sender<Notification>>,
+    // the reason, why the connection was closed
+    closed: Option<String>,
+}
+
+impl State {
+    fn close(&mut self, reason: String) {
+        for tx in self.pending.drain(..) {
+            let _ = tx.send(Err(Error::from(reason.clone())));
+        }
+        self.subscribers.clear();
+        self.closed = Some(reason);
+    }
+}
+
+// the transport of the reader thread and the callers
+#[derive(Debug)]
+struct Shared {
+    conn: Mutex<BufReader<Box<dyn Transport>>>,
+    // the separate writer of the transport. Without it the callers and the reader take turns
+    // on `conn`.
+    writer: Option<Mutex<Box<dyn Transport>>>,
+    // the number of callers, that wait for `conn`; the reader lets them go first
+    writers: Mutex<usize>,
+    writers_done: Condvar,
+    stopped: AtomicBool,
+    state: Mutex<State>,
+}
+
+impl Shared {
+    // closes the transport, that also ends a blocked read of the reader thread.
+    fn shutdown(&self) -> io::Result<()> {
+        match self.writer {
+            Some(ref writer) => lock(writer)?.shutdown(),
+            None => lock(&self.conn)?.get_mut().shutdown(),
+        }
+    }
+
+    // writes a command. The caller is added to the pending callers, while the transport is
+    // locked, so the order of the callers is the order of the commands.
+    fn write_command(
+        &self,
+        command: &str,
+        tx: mpsc::Sender<error::Result<String>>,
+    ) -> error::Result<()> {
+        let write = |stream: &mut dyn Transport| -> error::Result<()> {
+            {
+                let mut state = self.state.lock()?;
+                if let Some(ref reason) = state.closed {
+                    return Err(Error::from(reason.clone()));
+                }
+                state.pending.push_back(tx);
+            }
+            // a single write, so the line is not split into two TCP segments
+            let line = format!("{}\n", command);
+            if let Err(e) = stream
+                .write_all(line.as_bytes())
+                .and_then(|_| stream.flush())
+            {
+                // nobody can answer the command anymore
+                self.state.lock()?.close(format!("{}", e));
+                return Err(Error::from(e));
+            }
+            Ok(())
+        };
+
+        match self.writer {
+            Some(ref writer) => write(&mut **lock(writer)?),
+            None => {
+                *self.writers.lock()? += 1;
+                let conn = lock(&self.conn);
+                *self.writers.lock()? -= 1;
+                self.writers_done.notify_all();
+                write(&mut **conn?.get_mut())
+            }
+        }
+    }
+}
+
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> io::Result<MutexGuard<'_, T>> {
+    mutex
+        .lock()
+        .map_err(|_| io::Error::other("the transport is poisoned"))
+}
+
+#[derive(Debug)]
+struct Inner {
+    addr: String,
+    rate_limit: Mutex<RateLimit>,
+    shared: Arc<Shared>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // stops the reader thread
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        let _ = self.shared.shutdown();
+    }
+}
+
+/// SharedConnection is a cloneable Server Query connection, that can be used by many threads.
+///
+/// The commands of all clones are written one after another and a dedicated reader thread
+/// matches the answers to them in the same order. The notifications are sent to all
+/// subscribers. The connection is closed when the last clone is dropped.
+///
+/// All clones share one RateLimit, so together they stay below the flood limits of the
+/// server, and commands, that the server refused because of flooding, are retried.
+///
+/// # Example
+/// ```
+/// use sqlib::shared::SharedConnection;
+/// use sqlib::testing::{MockServer, Reply};
+/// use std::thread;
+///
+/// let server = MockServer::start().unwrap();
+/// server.on("whoami", Reply::ok("virtualserver_id=1"));
+///
+/// let conn = SharedConnection::new(&server.addr()).unwrap();
+/// let threads: Vec<_> = (0..4)
+///     .map(|_| {
+///         let conn = conn.clone();
+///         thread::spawn(move || conn.send_command(&"whoami").unwrap())
+///     })
+///     .collect();
+/// for thread in threads {
+///     assert_eq!(thread.join().unwrap().trim(), "virtualserver_id=1");
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SharedConnection {
+    inner: Arc<Inner>,
+}
+
+impl SharedConnection {
+    /// creates a new SharedConnection from an adress given as a string reference.
+    pub fn new(addr: &str) -> error::Result<SharedConnection> {
+        let a: net::SocketAddrV4 = addr.parse()?;
+        let stream = net::TcpStream::connect(a)?;
+        SharedConnection::with_transport(&a.to_string(), stream)
+    }
+
+    /// creates a new SharedConnection over an already connected Transport, e.g. the SSH or TLS
+    /// transport, and checks the banner of the server. The address is only used for
+    /// displaying the SharedConnection.
+    pub fn with_transport<T>(addr: &str, transport: T) -> error::Result<SharedConnection>
+    where
+        T: Transport + 'static,
+    {
+        let mut reader = BufReader::new(Box::new(transport) as Box<dyn Transport>);
+        let mut line = Vec::new();
+        reader.read_until(b'\n', &mut line)?;
+        if decode_line(&line).trim() != "TS3" {
+            return Err(Error::from("the given server is not a TS3 server"));
+        }
+        line.clear();
+        reader.read_until(b'\n', &mut line)?;
+        // with a separate writer the reader can block, otherwise it has to release the
+        // transport regularly
+        let writer = reader.get_ref().try_clone_writer()?;
+        if writer.is_none() {
+            reader.get_mut().set_read_timeout(Some(READ_TIMEOUT))?;
+        }
+
+        let shared = Arc::new(Shared {
+            conn: Mutex::new(reader),
+            writer: writer.map(Mutex::new),
+            writers: Mutex::new(0),
+            writers_done: Condvar::new(),
+            stopped: AtomicBool::new(false),
+            state: Mutex::new(State::default()),
+        });
+        let thread_shared = shared.clone();
+        thread::Builder::new()
+            .name(format!("sqlib reader {}", addr))
+            .spawn(move || read_loop(&thread_shared))?;
+
+        Ok(SharedConnection {
+            inner: Arc::new(Inner {
+                addr: addr.to_string(),
+                rate_limit: Mutex::new(RateLimit::default()),
+                shared,
+            }),
+        })
+    }
+
+    /// replaces the RateLimit, that all clones share. The default are the default flood limits
+    /// of the server.
+    pub fn set_rate_limit(&self, rate_limit: RateLimit) {
+        if let Ok(mut current) = self.inner.rate_limit.lock() {
+            *current = rate_limit;
+        }
+    }
+
+    /// sends a given command to the server and returns the answer as a String, or the error.
+    ///
+    /// The calling thread blocks until the command may be sent by the RateLimit and the answer
+    /// is received. Commands, that the server refused because of flooding, are retried.
+    pub fn send_command<C>(&self, command: &C) -> error::Result<String>
+    where
+        C: Command,
+    {
+        let command = command.string();
+        if command.is_empty() {
+            return Err(Error::from("no command"));
+        }
+
+        let mut retries = self.inner.rate_limit.lock()?.get_retries();
+        loop {
+            self.inner.rate_limit.lock()?.acquire();
+            match self.send_line(&command) {
+                Err(ref e) if e.is_flooding() && retries > 0 => {
+                    retries -= 1;
+                    self.inner.rate_limit.lock()?.backoff();
+                }
+                result => return result,
+            }
+        }
+    }
+
+    // writes a command and waits for its answer.
+    fn send_line(&self, command: &str) -> error::Result<String> {
+        let (tx, rx) = mpsc::channel();
+        self.inner.shared.write_command(command, tx)?;
+        rx.recv()
+            .unwrap_or_else(|_| Err(Error::from("the connection is closed")))
+    }
+
+    /// sends a given command to the server and parses the answer into the requested type.
+    pub fn query<T, C>(&self, command: &C) -> error::Result<T>
+    where
+        T: FromResponse,
+        C: Command,
+    {
+        let result = self.send_command(command)?;
+        T::from_response(&result)
+    }
+
+    /// returns a receiver for all notifications, that are received from now on.
+    ///
+    /// The receiver is disconnected when the connection is closed.
+    pub fn subscribe(&self) -> mpsc::Receiver<Notification> {
+        let (tx, rx) = mpsc::channel();
+        if let Ok(mut state) = self.inner.shared.state.lock() {
+            if state.closed.is_none() {
+                state.subscribers.push(tx);
+            }
+        }
+        rx
+    }
+
+    /// registers for the notifications of an event, see `Connection::register_notifications`.
+    pub fn register_notifications(&self, event: &str, id: Option<i64>) -> error::Result<()> {
+        let cmd = match id {
+            Some(id) => format!("servernotifyregister event={} id={}", event, id),
+            None => format!("servernotifyregister event={}", event),
+        };
+        self.send_command(&cmd).map(|_| ())
+    }
+
+    /// sends the quit command to the server and shuts the connection down for all clones.
+    pub fn quit(&self) -> error::Result<()> {
+        self.send_command(&"quit")?;
+        self.inner.shared.shutdown()?;
+        Ok(())
+    }
+
+    /// sends the use command with the given id to the server.
+    ///
+    /// The selected virtual server is changed for all clones.
+    pub fn use_server_id(&self, id: u64) -> error::Result<()> {
+        self.send_command(&format!("use {}", id)).map(|_| ())
+    }
+
+    /// sends the login command with the name and password to the server.
+    pub fn login(&self, name: &str, pw: &str) -> error::Result<()> {
+        self.send_command(&format!("login {} {}", name, pw))
+            .map(|_| ())
+    }
+
+    /// sends the clientlist command to the server and parses the result.
+    pub fn clientlist(&self) -> error::Result<ClientList> {
+        self.query(&"clientlist")
+    }
+
+    /// sends the channellist command to the server and parses the result.
+    pub fn channellist(&self) -> error::Result<ChannelList> {
+        self.query(&"channellist")
+    }
+
+    /// checks if the connection was closed.
+    pub fn is_closed(&self) -> bool {
+        self.inner
+            .shared
+            .state
+            .lock()
+            .map(|state| state.closed.is_some())
+            .unwrap_or(true)
+    }
+}
+
+impl fmt::Display for SharedConnection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", &self.inner.addr)
+    }
+}
+
+// reads the lines of the server until the connection is closed and routes them to the waiting
+// callers and the subscribers. Without a separate writer the transport is released after every
+// read, that timed out, so the callers can write their commands.
+fn read_loop(shared: &Shared) {
+    let mut collector = ReplyCollector::new();
+    let mut buf = Vec::new();
+    let reason = loop {
+        if shared.stopped.load(Ordering::SeqCst) {
+            return;
+        }
+        if shared.writer.is_none() {
+            let mut writers = match shared.writers.lock() {
+                Ok(writers) => writers,
+                Err(_) => return,
+            };
+            while *writers > 0 {
+                writers = match shared.writers_done.wait(writers) {
+                    Ok(writers) => writers,
+                    Err(_) => return,
+                };
+            }
+        }
+        let read = match shared.conn.lock() {
+            // a line, that was interrupted by the timeout, stays in the buffer
+            Ok(mut conn) => conn.read_until(b'\n', &mut buf),
+            Err(_) => return,
+        };
+        match read {
+            Ok(0) => break "the connection was closed by the server".to_string(),
+            Ok(_) if buf.ends_with(b"\n") => {}
+            Ok(_) => continue,
+            Err(ref e)
+                if e.kind() == io::ErrorKind::WouldBlock
+                    || e.kind() == io::ErrorKind::TimedOut
+                    || e.kind() == io::ErrorKind::Interrupted =>
+            {
+                continue
+            }
+            Err(e) => break format!("{}", e),
+        }
+        let line = decode_line(&buf);
+        buf.clear();
+        let mut state = match shared.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        match collector.push_line(&line) {
+            Line::Notification(notification) => {
+                state
+                    .subscribers
+                    .retain(|tx| tx.send(notification.clone()).is_ok());
+            }
+            Line::Reply(answer) => {
+                if let Some(tx) = state.pending.pop_front() {
+                    let _ = tx.send(answer);
+                }
+            }
+            Line::Partial => {}
+        }
+    };
+    if let Ok(mut state) = shared.state.lock() {
+        state.close(reason);
+    }
+}
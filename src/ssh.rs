@@ -0,0 +1,195 @@
+//! The ssh module contains the SshTransport struct, a Transport over the SSH query port of the
+//! server (10022 by default). It needs the `ssh` feature.
+//!
+//! The Server Query login is used for the SSH authentication, so no `login` command is needed.
+//! The host key is checked against a pinned fingerprint before the password is sent; it is
+//! printed by `ssh-keygen -l -E sha256 -f ssh_host_rsa_key.pub` on the server.
+//!
+//! # Example
+//! ```no_run
+//! use sqlib::connection::Connection;
+//!
+//! let fingerprint = "SHA256:7GOwLbpJLXL1e5ncQOaDNWBuTkODTuNOIRdJDnUDS4E";
+//! let mut conn =
+//!     Connection::new_ssh("127.0.0.1:10022", "serveradmin", "password", fingerprint).unwrap();
+//! conn.use_server_id(1).unwrap();
+//! println!("{}", conn.clientlist().unwrap());
+//! ```
+
+use error;
+use error::Error;
+use ssh2::{Channel, HashType, Session};
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::net;
+use std::time::Duration;
+use transport::Transport;
+
+/// the default SSH query port of the server
+pub const DEFAULT_SSH_PORT: u16 = 10022;
+/// the time the handshake and the authentication may take, e.g. if the host is no SSH server
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// SshTransport is a Transport over an interactive SSH session.
+pub struct SshTransport {
+    session: Session,
+    channel: Channel,
+    fingerprint: String,
+    // the timeout of reads in milliseconds, 0 means no timeout
+    read_timeout: u32,
+}
+
+impl SshTransport {
+    /// connects to the SSH query port and authenticates with the Server Query login, if the
+    /// SHA256 fingerprint of the host key matches the given one. The password is only sent to
+    /// a matching host.
+    ///
+    /// The fingerprint is either the output of `ssh-keygen -l -E sha256` (`SHA256:` and base64)
+    /// or hex encoded, colons are ignored.
+    pub fn connect_pinned(
+        addr: &str,
+        login: &str,
+        password: &str,
+        fingerprint: &str,
+    ) -> error::Result<SshTransport> {
+        SshTransport::connect_inner(addr, login, password, Some(fingerprint))
+    }
+
+    /// connects like `connect_pinned`, but the host key is not checked. Anybody, who can
+    /// intercept the connection, gets the password, so only use it in a trusted network.
+    pub fn connect_unverified(
+        addr: &str,
+        login: &str,
+        password: &str,
+    ) -> error::Result<SshTransport> {
+        SshTransport::connect_inner(addr, login, password, None)
+    }
+
+    fn connect_inner(
+        addr: &str,
+        login: &str,
+        password: &str,
+        pinned: Option<&str>,
+    ) -> error::Result<SshTransport> {
+        let stream = net::TcpStream::connect(addr)?;
+        let mut session = Session::new()?;
+        session.set_tcp_stream(stream);
+        session.set_timeout(CONNECT_TIMEOUT.as_millis() as u32);
+        session.handshake()?;
+
+        let hash = session
+            .host_key_hash(HashType::Sha256)
+            .ok_or_else(|| Error::from("the server sent no host key"))?;
+        let fingerprint = to_hex(hash);
+        if let Some(pinned) = pinned {
+            if !fingerprint_matches(pinned, hash) {
+                return Err(Error::from(format!(
+                    "the host key {} does not match the pinned key",
+                    fingerprint
+                )));
+            }
+        }
+
+        session.userauth_password(login, password)?;
+        if !session.authenticated() {
+            return Err(Error::from("the ssh authentication failed"));
+        }
+
+        // the query interface only answers on an interactive shell
+        let mut channel = session.channel_session()?;
+        channel.request_pty("raw", None, None)?;
+        channel.shell()?;
+        session.set_timeout(0);
+
+        Ok(SshTransport {
+            session,
+            channel,
+            fingerprint,
+            read_timeout: 0,
+        })
+    }
+
+    /// returns the hex encoded SHA256 fingerprint of the host key.
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// encodes the bytes in base64 without padding like `ssh-keygen`.
+fn to_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    out
+}
+
+// compares a pinned fingerprint in one of the formats of `connect_pinned` with a host key hash.
+fn fingerprint_matches(pinned: &str, hash: &[u8]) -> bool {
+    let pinned = pinned.trim();
+    match pinned.strip_prefix("SHA256:") {
+        Some(base64) => base64.trim_end_matches('=') == to_base64(hash),
+        None => pinned.replace(':', "").to_lowercase() == to_hex(hash),
+    }
+}
+
+impl Read for SshTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // the timeout of the session applies to writes too, so it is only set while reading
+        if self.read_timeout == 0 {
+            return self.channel.read(buf);
+        }
+        self.session.set_timeout(self.read_timeout);
+        let read = self.channel.read(buf);
+        self.session.set_timeout(0);
+        read
+    }
+}
+
+impl Write for SshTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.channel.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.channel.flush()
+    }
+}
+
+impl Transport for SshTransport {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.read_timeout = match timeout {
+            Some(timeout) => timeout.as_millis().max(1).min(u128::from(u32::MAX)) as u32,
+            None => 0,
+        };
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> io::Result<()> {
+        // the server closes the channel after `quit` itself
+        let _ = self.channel.send_eof();
+        let _ = self.channel.close();
+        let _ = self.session.disconnect(None, "quit", None);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for SshTransport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SshTransport")
+            .field("fingerprint", &self.fingerprint)
+            .finish()
+    }
+}
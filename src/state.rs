@@ -0,0 +1,242 @@
+//! The state module contains the ServerState struct, a local mirror of the channels and clients
+//! of a virtual server, that is kept up to date by notifications.
+//!
+//! # Example
+//! ```no_run
+//! use sqlib::connection::Connection;
+//! use sqlib::state::ServerState;
+//! use std::time::Duration;
+//!
+//! let mut conn = Connection::new("127.0.0.1:10011").unwrap();
+//! conn.login("serveradmin", "password").unwrap();
+//! conn.use_server_id(1).unwrap();
+//!
+//! let mut state = ServerState::load(&mut conn).unwrap();
+//! loop {
+//!     state.wait(&mut conn, Duration::from_secs(1)).unwrap();
+//!     println!("{}", state.channels().as_json().unwrap());
+//! }
+//! ```
+
+use channel::{Channel, ChannelList};
+use client::Client;
+use connection::Connection;
+use error;
+use map::*;
+use notification::Notification;
+use std::time::Duration;
+
+/// ServerState mirrors the channels of a virtual server together with their clients.
+///
+/// It is loaded with one `channellist` and one `clientlist` command and then updated by the
+/// client and channel notifications, so reading it costs no round trips to the server.
+///
+/// # Example
+/// ```
+/// use sqlib::channel::{Channel, ChannelList};
+/// use sqlib::notification::Notification;
+/// use sqlib::state::ServerState;
+///
+/// let channels = ChannelList::from(vec![
+///     Channel::new(1, "lobby".to_string()),
+///     Channel::new(2, "games".to_string()),
+/// ]);
+/// let mut state = ServerState::from(channels);
+///
+/// let enter = "notifycliententerview cfid=0 ctid=1 reasonid=0 clid=5 client_nickname=test";
+/// state.apply(&Notification::parse(enter).unwrap());
+/// assert_eq!(state.channels()[0].clients_len(), 1);
+///
+/// state.apply(&Notification::parse("notifyclientmoved ctid=2 reasonid=0 clid=5").unwrap());
+/// assert_eq!(state.channels()[0].clients_len(), 0);
+/// assert_eq!(state.channels()[1].clients[0].client_nickname, "test");
+///
+/// state.apply(&Notification::parse("notifyclientleftview cfid=2 ctid=0 clid=5").unwrap());
+/// assert!(state.channels()[1].is_empty());
+/// ```
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ServerState {
+    channels: ChannelList,
+    // the dropped notifications of the Connection, when it was loaded
+    #[cfg_attr(feature = "serde", serde(skip))]
+    dropped_notifications: u64,
+}
+
+impl ServerState {
+    /// registers for the `server` and `channel` notifications and loads the channels and
+    /// clients of the selected virtual server.
+    ///
+    /// Notifications, that arrive while loading, are queued in the Connection and applied by
+    /// the next call of `update` or `wait`.
+    pub fn load(conn: &mut Connection) -> error::Result<ServerState> {
+        conn.register_notifications("server", None)?;
+        conn.register_notifications("channel", Some(0))?;
+        let mut state = ServerState::default();
+        state.reload(conn)?;
+        Ok(state)
+    }
+
+    /// loads the channels and clients again, without applying the queued notifications.
+    pub fn reload(&mut self, conn: &mut Connection) -> error::Result<()> {
+        self.dropped_notifications = conn.dropped_notifications();
+        let clients = conn.clientlist()?;
+        let mut channels = conn.channellist()?;
+        channels.merge_clients(&clients);
+        self.channels = channels;
+        Ok(())
+    }
+
+    /// returns the current channels with their clients.
+    pub fn channels(&self) -> &ChannelList {
+        &self.channels
+    }
+
+    /// returns a copy of the current channels with their clients.
+    pub fn snapshot(&self) -> ChannelList {
+        self.channels.clone()
+    }
+
+    /// applies all notifications, that are queued in the Connection, without reading from the
+    /// server. It returns the number of applied notifications.
+    ///
+    /// If the Connection dropped notifications, because its queue was full, the mirror would
+    /// miss changes. Then the queued notifications are discarded and the channels and clients
+    /// are loaded again instead.
+    pub fn update(&mut self, conn: &mut Connection) -> error::Result<usize> {
+        let notifications = conn.take_notifications();
+        if conn.dropped_notifications() != self.dropped_notifications {
+            self.reload(conn)?;
+            return Ok(0);
+        }
+        for notification in &notifications {
+            self.apply(notification);
+        }
+        Ok(notifications.len())
+    }
+
+    /// waits up to the timeout for a notification and then applies all queued notifications
+    /// like `update`. It returns the number of applied notifications.
+    pub fn wait(&mut self, conn: &mut Connection, timeout: Duration) -> error::Result<usize> {
+        match conn.wait_notification_timeout(timeout)? {
+            Some(notification) => {
+                self.apply(&notification);
+                Ok(self.update(conn)? + 1)
+            }
+            None => self.update(conn),
+        }
+    }
+
+    /// applies a single notification. Notifications, that do not change the channels or
+    /// clients, are ignored.
+    ///
+    /// Applying a notification twice has no effect, so notifications, that arrived while
+    /// loading, can be applied safely.
+    pub fn apply(&mut self, notification: &Notification) {
+        for map in notification.maps() {
+            match notification.name() {
+                "notifycliententerview" => self.client_enter(map),
+                "notifyclientleftview" => self.client_left(map),
+                "notifyclientmoved" => self.client_moved(map),
+                "notifychannelcreated" => self.channel_created(map),
+                "notifychanneledited" => self.channel_edited(map),
+                "notifychanneldeleted" => self.channel_deleted(map),
+                "notifychannelmoved" => self.channel_moved(map),
+                _ => {}
+            }
+        }
+    }
+
+    fn client_enter(&mut self, map: &StringMap) {
+        let mut client = Client::from_map(map);
+        update_from_map(map, "ctid", &mut client.cid);
+        self.channels.remove_client(client.clid);
+        self.channels.insert_client(&client);
+    }
+
+    fn client_left(&mut self, map: &StringMap) {
+        let mut clid = 0;
+        update_from_map(map, "clid", &mut clid);
+        self.channels.remove_client(clid);
+    }
+
+    fn client_moved(&mut self, map: &StringMap) {
+        let mut clid = 0;
+        let mut ctid = 0;
+        update_from_map(map, "clid", &mut clid);
+        update_from_map(map, "ctid", &mut ctid);
+        if let Some(mut client) = self.channels.remove_client(clid) {
+            client.cid = ctid;
+            self.channels.insert_client(&client);
+        }
+    }
+
+    fn channel_created(&mut self, map: &StringMap) {
+        let mut channel = Channel::from_map(map);
+        update_from_map(map, "cpid", &mut channel.pid);
+        self.remove_channel(channel.cid);
+        self.insert_channel(channel);
+    }
+
+    fn channel_edited(&mut self, map: &StringMap) {
+        let mut cid = 0;
+        update_from_map(map, "cid", &mut cid);
+        if let Some(channel) = self.channels.channel_mut(cid) {
+            // the position is only changed by notifychannelmoved
+            let (pid, order) = (channel.pid, channel.channel_order);
+            channel.mut_from_map(map);
+            channel.pid = pid;
+            channel.channel_order = order;
+        }
+    }
+
+    fn channel_deleted(&mut self, map: &StringMap) {
+        let mut cid = 0;
+        update_from_map(map, "cid", &mut cid);
+        self.remove_channel(cid);
+    }
+
+    fn channel_moved(&mut self, map: &StringMap) {
+        let mut cid = 0;
+        update_from_map(map, "cid", &mut cid);
+        if let Some(mut channel) = self.remove_channel(cid) {
+            update_from_map(map, "cpid", &mut channel.pid);
+            update_from_map(map, "order", &mut channel.channel_order);
+            self.insert_channel(channel);
+        }
+    }
+
+    // inserts the channel at its position among its siblings: the channel, that was below the
+    // new channel's predecessor, is now below the new channel.
+    fn insert_channel(&mut self, channel: Channel) {
+        let (cid, pid, order) = (channel.cid, channel.pid, channel.channel_order);
+        for sibling in self.channels.as_mut().iter_mut() {
+            if sibling.pid == pid && sibling.channel_order == order {
+                sibling.channel_order = cid;
+            }
+        }
+        self.channels.as_mut().push(channel);
+        self.channels.sort_tree();
+    }
+
+    // removes the channel and closes the gap among its siblings.
+    fn remove_channel(&mut self, cid: i64) -> Option<Channel> {
+        let pos = self.channels.iter().position(|c| c.cid == cid)?;
+        let channel = self.channels.as_mut().remove(pos);
+        for sibling in self.channels.as_mut().iter_mut() {
+            if sibling.pid == channel.pid && sibling.channel_order == cid {
+                sibling.channel_order = channel.channel_order;
+            }
+        }
+        Some(channel)
+    }
+}
+
+impl From<ChannelList> for ServerState {
+    fn from(channels: ChannelList) -> ServerState {
+        ServerState {
+            channels,
+            dropped_notifications: 0,
+        }
+    }
+}
@@ -0,0 +1,301 @@
+//! The testing module contains the MockServer struct, an in-process stand-in for a TS3 Server
+//! Query interface, to test code built on a Connection without a real server.
+//!
+//! # Example
+//! ```
+//! use sqlib::connection::Connection;
+//! use sqlib::testing::{MockServer, Reply};
+//!
+//! let server = MockServer::start().unwrap();
+//! server.on("clientlist", Reply::ok("clid=1 cid=1 client_database_id=1 client_nickname=test client_type=0"));
+//! server.once("login", Reply::error(520, "invalid loginname or password"));
+//!
+//! let mut conn = Connection::new(&server.addr()).unwrap();
+//! assert!(conn.login("serveradmin", "wrong").is_err());
+//! assert!(conn.login("serveradmin", "password").is_ok());
+//! assert_eq!(conn.clientlist().unwrap().as_ref()[0].client_nickname, "test");
+//!
+//! assert_eq!(server.received()[0], "login serveradmin wrong");
+//! ```
+
+use error;
+use escaping::escape;
+use std::collections::VecDeque;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// the welcome line, that the server sends after the `TS3` line
+pub const WELCOME: &str = "Welcome to the TeamSpeak 3 ServerQuery interface, type \"help\" for a \
+                           list of commands and \"help <command>\" for information on a specific \
+                           command.";
+
+/// Reply is the scripted answer of the MockServer to a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    /// the data lines, that are sent before `error id=0 msg=ok`; they may be empty
+    Ok(String),
+    /// an error with an id and an unescaped message
+    Error(u32, String),
+    /// closes the connection without an answer
+    Drop,
+}
+
+impl Reply {
+    /// creates a successful Reply with the given data.
+    pub fn ok(data: &str) -> Reply {
+        Reply::Ok(data.to_string())
+    }
+
+    /// creates an error Reply.
+    pub fn error(id: u32, msg: &str) -> Reply {
+        Reply::Error(id, msg.to_string())
+    }
+
+    // creates the lines, that are sent to the client.
+    fn lines(&self) -> String {
+        match *self {
+            Reply::Ok(ref data) if data.is_empty() => "error id=0 msg=ok\n\r".to_string(),
+            Reply::Ok(ref data) => format!("{}\n\rerror id=0 msg=ok\n\r", data),
+            Reply::Error(id, ref msg) => format!("error id={} msg={}\n\r", id, escape(msg)),
+            Reply::Drop => String::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Rule {
+    command: String,
+    reply: Reply,
+}
+
+impl Rule {
+    // a rule matches the command itself and the command with parameters.
+    fn matches(&self, line: &str) -> bool {
+        line == self.command
+            || (line.starts_with(&self.command)
+                && line[self.command.len()..].starts_with(char::is_whitespace))
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    rules: Vec<Rule>,
+    once: VecDeque<Rule>,
+    received: Vec<String>,
+    clients: Vec<Arc<Mutex<net::TcpStream>>>,
+    accepted: usize,
+    stopped: bool,
+}
+
+impl State {
+    // returns the reply for a command: one-time replies first, then the latest matching rule.
+    fn reply_for(&mut self, line: &str) -> Reply {
+        if let Some(pos) = self.once.iter().position(|rule| rule.matches(line)) {
+            if let Some(rule) = self.once.remove(pos) {
+                return rule.reply;
+            }
+        }
+        if let Some(rule) = self.rules.iter().rev().find(|rule| rule.matches(line)) {
+            return rule.reply.clone();
+        }
+        match line.split_whitespace().next() {
+            Some("quit") | Some("login") | Some("use") | Some("logout") => Reply::ok(""),
+            Some("servernotifyregister") | Some("servernotifyunregister") => Reply::ok(""),
+            _ => Reply::error(256, "command not found"),
+        }
+    }
+}
+
+/// MockServer is a local Server Query stand-in, that answers commands with scripted replies.
+///
+/// Without a rule `login`, `use`, `logout`, `quit` and the notification registration succeed
+/// and all other commands fail with `error id=256 msg=command\snot\sfound`, like on a real
+/// server. The server stops, when the MockServer is dropped.
+#[derive(Debug)]
+pub struct MockServer {
+    addr: net::SocketAddr,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockServer {
+    /// binds a free local port and starts to accept connections.
+    pub fn start() -> error::Result<MockServer> {
+        let listener = net::TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let accept_state = state.clone();
+        thread::Builder::new()
+            .name("sqlib mock server".to_string())
+            .spawn(move || accept(&listener, &accept_state))?;
+
+        Ok(MockServer { addr, state })
+    }
+
+    /// returns the address of the server, e.g. for `Connection::new`.
+    pub fn addr(&self) -> String {
+        self.addr.to_string()
+    }
+
+    /// answers every command, that starts with `command`, with the reply. A later rule for the
+    /// same command replaces an earlier one.
+    pub fn on(&self, command: &str, reply: Reply) {
+        if let Ok(mut state) = self.state.lock() {
+            state.rules.push(Rule {
+                command: command.to_string(),
+                reply,
+            });
+        }
+    }
+
+    /// answers the next command, that starts with `command`, with the reply. One-time replies
+    /// are used before the rules of `on` and in the order they were added.
+    pub fn once(&self, command: &str, reply: Reply) {
+        if let Ok(mut state) = self.state.lock() {
+            state.once.push_back(Rule {
+                command: command.to_string(),
+                reply,
+            });
+        }
+    }
+
+    /// sends a notification line, e.g. `notifyclientleftview cfid=1 ctid=0 clid=5`, to all
+    /// connected clients.
+    ///
+    /// # Example
+    /// ```
+    /// use sqlib::connection::Connection;
+    /// use sqlib::testing::MockServer;
+    ///
+    /// let server = MockServer::start().unwrap();
+    /// let mut conn = Connection::new(&server.addr()).unwrap();
+    /// conn.register_notifications("server", None).unwrap();
+    ///
+    /// server.notify("notifyclientleftview cfid=1 ctid=0 clid=5");
+    /// assert_eq!(conn.wait_notification().unwrap().get("clid"), Some("5"));
+    ///
+    /// server.drop_connections();
+    /// assert!(conn.send_command(&"whoami").is_err());
+    /// ```
+    pub fn notify(&self, notification: &str) {
+        let clients = match self.state.lock() {
+            Ok(state) => state.clients.clone(),
+            Err(_) => return,
+        };
+        for client in clients {
+            if let Ok(mut stream) = client.lock() {
+                let _ = write!(stream, "{}\n\r", notification);
+            }
+        }
+    }
+
+    /// closes all open connections. New connections are still accepted.
+    pub fn drop_connections(&self) {
+        let clients = match self.state.lock() {
+            Ok(mut state) => state.clients.drain(..).collect::<Vec<_>>(),
+            Err(_) => return,
+        };
+        for client in clients {
+            if let Ok(stream) = client.lock() {
+                let _ = stream.shutdown(net::Shutdown::Both);
+            }
+        }
+    }
+
+    /// returns all commands, that were received, in their order.
+    pub fn received(&self) -> Vec<String> {
+        self.state
+            .lock()
+            .map(|state| state.received.clone())
+            .unwrap_or_default()
+    }
+
+    /// returns the number of connections, that were accepted.
+    pub fn connections(&self) -> usize {
+        self.state.lock().map(|state| state.accepted).unwrap_or(0)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.stopped = true;
+        }
+        self.drop_connections();
+        // wakes up the accepting thread
+        let _ = net::TcpStream::connect(self.addr);
+    }
+}
+
+fn accept(listener: &net::TcpListener, state: &Arc<Mutex<State>>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        let reader = match stream.try_clone() {
+            Ok(reader) => reader,
+            Err(_) => continue,
+        };
+        let client = Arc::new(Mutex::new(stream));
+        match state.lock() {
+            Ok(ref state) if state.stopped => return,
+            Ok(mut state) => {
+                state.accepted += 1;
+                state.clients.push(client.clone());
+            }
+            Err(_) => return,
+        }
+
+        let state = state.clone();
+        thread::spawn(move || {
+            let _ = serve(reader, &client, &state);
+            if let Ok(stream) = client.lock() {
+                let _ = stream.shutdown(net::Shutdown::Both);
+            }
+            if let Ok(mut state) = state.lock() {
+                state.clients.retain(|other| !Arc::ptr_eq(other, &client));
+            }
+        });
+    }
+}
+
+// sends the banner and answers the commands of a single connection.
+fn serve(
+    reader: net::TcpStream,
+    client: &Mutex<net::TcpStream>,
+    state: &Mutex<State>,
+) -> io::Result<()> {
+    write!(lock(client)?, "TS3\n\r{}\n\r", WELCOME)?;
+
+    for line in BufReader::new(reader).lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let reply = {
+            let mut state = lock(state)?;
+            state.received.push(line.to_string());
+            state.reply_for(line)
+        };
+        if reply == Reply::Drop {
+            return Ok(());
+        }
+        write!(lock(client)?, "{}", reply.lines())?;
+        if line == "quit" {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> io::Result<::std::sync::MutexGuard<'_, T>> {
+    mutex
+        .lock()
+        .map_err(|_| io::Error::other("the mock server panicked"))
+}
@@ -0,0 +1,54 @@
+//! The textmessage module contains the TextMessageTarget enum, the receiver of a
+//! `sendtextmessage` command.
+
+use escaping::escape;
+
+/// TextMessageTarget is the receiver of a text message.
+///
+/// # Example
+/// ```
+/// use sqlib::textmessage::TextMessageTarget;
+///
+/// let target = TextMessageTarget::Client(5);
+///
+/// assert_eq!(target.command("hello world"), "sendtextmessage targetmode=1 target=5 msg=hello\\sworld");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TextMessageTarget {
+    /// a client with the given client id
+    Client(i64),
+    /// the channel the Server Query client is in
+    Channel,
+    /// the selected virtual server
+    Server,
+}
+
+impl TextMessageTarget {
+    /// returns the `targetmode` of the target.
+    pub fn target_mode(&self) -> u8 {
+        match *self {
+            TextMessageTarget::Client(_) => 1,
+            TextMessageTarget::Channel => 2,
+            TextMessageTarget::Server => 3,
+        }
+    }
+
+    /// returns the `target` of the target. The server ignores it for channels and servers.
+    pub fn target(&self) -> i64 {
+        match *self {
+            TextMessageTarget::Client(clid) => clid,
+            _ => 0,
+        }
+    }
+
+    /// creates the `sendtextmessage` command for the message.
+    pub fn command(&self, msg: &str) -> String {
+        format!(
+            "sendtextmessage targetmode={} target={} msg={}",
+            self.target_mode(),
+            self.target(),
+            escape(msg)
+        )
+    }
+}
@@ -0,0 +1,223 @@
+//! The tls module contains the TlsTransport struct, a Transport over TLS, e.g. to a TLS
+//! terminating proxy like stunnel in front of the query port. It needs the `tls` feature.
+//!
+//! # Example
+//! ```no_run
+//! use sqlib::connection::Connection;
+//! use sqlib::tls::TlsConfig;
+//!
+//! let config = TlsConfig::new()
+//!     .ca_file("/etc/ssl/query-ca.pem")
+//!     .unwrap()
+//!     .server_name("query.example.com");
+//!
+//! let mut conn = Connection::new_tls("10.0.0.5:10443", &config).unwrap();
+//! conn.login("serveradmin", "password").unwrap();
+//! ```
+
+use error;
+use error::Error;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::net;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use transport::Transport;
+
+/// TlsConfig contains the settings of a TLS connection.
+///
+/// Without a CA the Mozilla root certificates are trusted. The server name defaults to the host
+/// of the address.
+#[derive(Debug, Default)]
+pub struct TlsConfig {
+    ca: Vec<CertificateDer<'static>>,
+    server_name: Option<String>,
+    client_auth: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+}
+
+impl Clone for TlsConfig {
+    fn clone(&self) -> TlsConfig {
+        TlsConfig {
+            ca: self.ca.clone(),
+            server_name: self.server_name.clone(),
+            client_auth: self
+                .client_auth
+                .as_ref()
+                .map(|(certs, key)| (certs.clone(), key.clone_key())),
+        }
+    }
+}
+
+impl TlsConfig {
+    /// creates a new TlsConfig, that trusts the Mozilla root certificates.
+    pub fn new() -> TlsConfig {
+        TlsConfig::default()
+    }
+
+    /// trusts the CA certificates of a PEM file instead of the Mozilla root certificates.
+    pub fn ca_file<P: AsRef<Path>>(self, path: P) -> error::Result<TlsConfig> {
+        let pem = read_file(path.as_ref())?;
+        self.ca_pem(&pem)
+    }
+
+    /// trusts the CA certificates of PEM data instead of the Mozilla root certificates.
+    pub fn ca_pem(mut self, pem: &[u8]) -> error::Result<TlsConfig> {
+        let certs = parse_certs(pem)?;
+        self.ca.extend(certs);
+        Ok(self)
+    }
+
+    /// sets the name, that is sent with SNI and that the certificate is checked against.
+    pub fn server_name(mut self, name: &str) -> TlsConfig {
+        self.server_name = Some(name.to_string());
+        self
+    }
+
+    /// authenticates with the client certificate chain and private key of the PEM files.
+    pub fn client_cert_files<P, Q>(self, cert: P, key: Q) -> error::Result<TlsConfig>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let cert = read_file(cert.as_ref())?;
+        let key = read_file(key.as_ref())?;
+        self.client_cert_pem(&cert, &key)
+    }
+
+    /// authenticates with the client certificate chain and private key of the PEM data.
+    pub fn client_cert_pem(mut self, cert: &[u8], key: &[u8]) -> error::Result<TlsConfig> {
+        let certs = parse_certs(cert)?;
+        let key = PrivateKeyDer::from_pem_slice(key)
+            .map_err(|e| Error::from(format!("invalid private key: {}", e)))?;
+        self.client_auth = Some((certs, key));
+        Ok(self)
+    }
+
+    // builds the rustls configuration.
+    fn client_config(&self) -> error::Result<ClientConfig> {
+        let mut roots = RootCertStore::empty();
+        if self.ca.is_empty() {
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        } else {
+            for cert in &self.ca {
+                roots
+                    .add(cert.clone())
+                    .map_err(|e| Error::from(format!("invalid CA certificate: {}", e)))?;
+            }
+        }
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(tls_error)?
+            .with_root_certificates(roots);
+        match self.client_auth {
+            Some((ref certs, ref key)) => builder
+                .with_client_auth_cert(certs.clone(), key.clone_key())
+                .map_err(tls_error),
+            None => Ok(builder.with_no_client_auth()),
+        }
+    }
+
+    // returns the name for SNI and the certificate check.
+    fn name_for(&self, addr: &str) -> error::Result<ServerName<'static>> {
+        let name = match self.server_name {
+            Some(ref name) => name.clone(),
+            None => host_of(addr).to_string(),
+        };
+        ServerName::try_from(name).map_err(|e| Error::from(format!("invalid server name: {}", e)))
+    }
+}
+
+fn read_file(path: &Path) -> error::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    ::std::fs::File::open(path)?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn parse_certs(pem: &[u8]) -> error::Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_slice_iter(pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| Error::from(format!("invalid certificate: {}", e)))?;
+    if certs.is_empty() {
+        return Err(Error::from("no certificate found"));
+    }
+    Ok(certs)
+}
+
+fn tls_error(err: rustls::Error) -> Error {
+    Error::from(format!("tls: {}", err))
+}
+
+// returns the host of an address like `host:port` or `[::1]:port`.
+fn host_of(addr: &str) -> &str {
+    let host = match addr.rfind(':') {
+        Some(i) if !addr[i + 1..].contains(']') => &addr[..i],
+        _ => addr,
+    };
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+/// TlsTransport is a Transport over a TLS connection.
+pub struct TlsTransport {
+    stream: StreamOwned<ClientConnection, net::TcpStream>,
+}
+
+impl TlsTransport {
+    /// connects to the address and completes the TLS handshake.
+    pub fn connect(addr: &str, config: &TlsConfig) -> error::Result<TlsTransport> {
+        let name = config.name_for(addr)?;
+        let client =
+            ClientConnection::new(Arc::new(config.client_config()?), name).map_err(tls_error)?;
+        let sock = net::TcpStream::connect(addr)?;
+        let mut stream = StreamOwned::new(client, sock);
+        // fail early with the TLS error instead of a later read error
+        while stream.conn.is_handshaking() {
+            stream.conn.complete_io(&mut stream.sock)?;
+        }
+        Ok(TlsTransport { stream })
+    }
+}
+
+impl Read for TlsTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl Write for TlsTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl Transport for TlsTransport {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.stream.sock.set_read_timeout(timeout)
+    }
+
+    fn shutdown(&mut self) -> io::Result<()> {
+        self.stream.conn.send_close_notify();
+        // the server may have closed the connection after `quit` already
+        let _ = self.stream.flush();
+        self.stream.sock.shutdown(net::Shutdown::Both)
+    }
+}
+
+impl fmt::Debug for TlsTransport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TlsTransport")
+            .field("peer", &self.stream.sock.peer_addr().ok())
+            .finish()
+    }
+}
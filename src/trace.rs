@@ -0,0 +1,189 @@
+//! The trace module contains the tracing of the query protocol and the redaction of secrets in
+//! commands.
+//!
+//! With the `tracing` feature a Connection creates `tracing` spans and events:
+//!
+//! - a `connect` span with the address around `Connection::new`,
+//! - a `command` span with the verb and the redacted command around every `send_command` and an
+//!   event with the duration, the size of the answer and the error id, when it is finished,
+//! - a `batch` span around `send_batch`,
+//! - an event for every notification and for every backoff after a flood error.
+//!
+//! The answers are never traced, because some of them contain secrets, e.g. the password of
+//! `queryloginadd`.
+//!
+//! # Example
+//! ```
+//! use sqlib::trace::redact;
+//!
+//! assert_eq!(redact("login serveradmin secret"), "login serveradmin ***");
+//! assert_eq!(
+//!     redact("login client_login_name=bot client_login_password=secret"),
+//!     "login client_login_name=bot client_login_password=***"
+//! );
+//! assert_eq!(redact("auth apikey=ABCD"), "auth apikey=***");
+//! assert_eq!(redact("clientlist -uid"), "clientlist -uid");
+//! ```
+
+/// the replacement of a secret value
+pub const REDACTED: &str = "***";
+
+// keys, whose values are secrets, besides all keys with `password` in their name
+const SECRET_KEYS: [&str; 4] = ["apikey", "token", "tokenkey", "client_login_password"];
+
+fn is_secret(key: &str) -> bool {
+    key.contains("password") || SECRET_KEYS.contains(&key)
+}
+
+/// returns the command with all secret values replaced, so it can be logged.
+///
+/// The password of `login name password` and the values of all keys like
+/// `client_login_password`, `virtualserver_password`, `apikey` and `token` are replaced.
+pub fn redact(command: &str) -> String {
+    let mut parts = command.split(' ');
+    let verb = parts.next().unwrap_or("");
+    let mut redacted = vec![verb.to_string()];
+    let mut position = 0;
+    for part in parts {
+        if part.is_empty() {
+            redacted.push(String::new());
+            continue;
+        }
+        position += 1;
+        let mut kv = part.splitn(2, '=');
+        let key = kv.next().unwrap_or("");
+        match kv.next() {
+            Some(_) if is_secret(key) => redacted.push(format!("{}={}", key, REDACTED)),
+            // the password of `login name password`
+            None if verb == "login" && position > 1 => redacted.push(REDACTED.to_string()),
+            _ => redacted.push(part.to_string()),
+        }
+    }
+    redacted.join(" ")
+}
+
+/// returns a line of the server with the values of all secret keys replaced, e.g. the password
+/// in the answer of `queryloginadd` or the key in the answer of `apikeyadd`.
+///
+/// # Example
+/// ```
+/// use sqlib::trace::redact_reply;
+///
+/// assert_eq!(
+///     redact_reply("cldbid=2 client_login_name=bot client_login_password=secret|cldbid=3"),
+///     "cldbid=2 client_login_name=bot client_login_password=***|cldbid=3"
+/// );
+/// assert_eq!(redact_reply("error id=0 msg=ok"), "error id=0 msg=ok");
+/// ```
+pub fn redact_reply(line: &str) -> String {
+    line.split('|')
+        .map(|record| {
+            record
+                .split(' ')
+                .map(|part| match part.split_once('=') {
+                    Some((key, _)) if is_secret(key) => format!("{}={}", key, REDACTED),
+                    _ => part.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+// returns the first word of a command.
+#[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+fn verb(command: &str) -> &str {
+    command.split_whitespace().next().unwrap_or("")
+}
+
+pub(crate) use self::hooks::*;
+
+#[cfg(feature = "tracing")]
+mod hooks {
+    use super::{redact, verb};
+    use error::{Error, Result};
+    use notification::Notification;
+    use std::time::Duration;
+    use tracing::span::EnteredSpan;
+
+    pub(crate) type Guard = EnteredSpan;
+
+    pub(crate) fn connect_span(addr: &str) -> Guard {
+        ::tracing::info_span!("connect", addr = addr).entered()
+    }
+
+    pub(crate) fn connected<T>(result: &Result<T>) {
+        match *result {
+            Ok(_) => ::tracing::debug!("connected"),
+            Err(ref e) => ::tracing::warn!(error = %e, "connecting failed"),
+        }
+    }
+
+    pub(crate) fn command_span(command: &str) -> Guard {
+        ::tracing::debug_span!("command", verb = verb(command), command = %redact(command))
+            .entered()
+    }
+
+    pub(crate) fn batch_span(commands: usize) -> Guard {
+        ::tracing::debug_span!("batch", commands = commands).entered()
+    }
+
+    pub(crate) fn command_finished(result: &Result<String>, elapsed: Duration) {
+        let duration_ms = elapsed.as_secs_f64() * 1000.0;
+        match *result {
+            Ok(ref reply) => ::tracing::debug!(duration_ms, reply_bytes = reply.len(), "ok"),
+            Err(Error::SQ(ref e)) => {
+                ::tracing::debug!(duration_ms, error_id = e.id(), msg = %e.msg(), "error")
+            }
+            Err(ref e) => ::tracing::warn!(duration_ms, error = %e, "failed"),
+        }
+    }
+
+    pub(crate) fn notification(notification: &Notification) {
+        ::tracing::debug!(name = notification.name(), "notification");
+    }
+
+    pub(crate) fn flood_backoff(retries_left: u32) {
+        ::tracing::warn!(
+            retries_left,
+            "the server refused a command because of flooding"
+        );
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+mod hooks {
+    use error::Result;
+    use notification::Notification;
+    use std::time::Duration;
+
+    pub(crate) struct Guard;
+
+    #[inline]
+    pub(crate) fn connect_span(_addr: &str) -> Guard {
+        Guard
+    }
+
+    #[inline]
+    pub(crate) fn connected<T>(_result: &Result<T>) {}
+
+    #[inline]
+    pub(crate) fn command_span(_command: &str) -> Guard {
+        Guard
+    }
+
+    #[inline]
+    pub(crate) fn batch_span(_commands: usize) -> Guard {
+        Guard
+    }
+
+    #[inline]
+    pub(crate) fn command_finished(_result: &Result<String>, _elapsed: Duration) {}
+
+    #[inline]
+    pub(crate) fn notification(_notification: &Notification) {}
+
+    #[inline]
+    pub(crate) fn flood_backoff(_retries_left: u32) {}
+}
@@ -0,0 +1,110 @@
+//! The transport module contains the Transport trait, the byte stream a Connection sends its
+//! commands over.
+//!
+//! The plain TCP stream of the raw query port is the default Transport. Other transports, like
+//! the SSH query port, are enabled by cargo features.
+//!
+//! # Example
+//! ```no_run
+//! use sqlib::connection::Connection;
+//! use std::net::TcpStream;
+//!
+//! let stream = TcpStream::connect("127.0.0.1:10011").unwrap();
+//! let mut conn = Connection::with_transport("127.0.0.1:10011", stream).unwrap();
+//! conn.login("serveradmin", "password").unwrap();
+//! ```
+
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::net;
+use std::time::Duration;
+
+/// Transport is a bidirectional byte stream to a Server Query interface.
+///
+/// # Example
+/// ```
+/// use sqlib::connection::Connection;
+/// use sqlib::transport::Transport;
+/// use std::io::{self, Cursor, Read, Write};
+/// use std::time::Duration;
+///
+/// // answers every command with the same scripted lines
+/// #[derive(Debug)]
+/// struct Scripted(Cursor<Vec<u8>>);
+///
+/// impl Read for Scripted {
+///     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+///         self.0.read(buf)
+///     }
+/// }
+///
+/// impl Write for Scripted {
+///     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+///         Ok(buf.len())
+///     }
+///     fn flush(&mut self) -> io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// impl Transport for Scripted {
+///     fn set_read_timeout(&mut self, _: Option<Duration>) -> io::Result<()> {
+///         Ok(())
+///     }
+///     fn shutdown(&mut self) -> io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// let script = "TS3\n\rWelcome\n\rvirtualserver_id=1\n\rerror id=0 msg=ok\n\r";
+/// let transport = Scripted(Cursor::new(script.as_bytes().to_vec()));
+///
+/// let mut conn = Connection::with_transport("scripted", transport).unwrap();
+/// let whoami = conn.send_command_to_map(&"whoami").unwrap();
+/// assert_eq!(whoami["virtualserver_id"], "1");
+/// ```
+pub trait Transport: Read + Write + Send + fmt::Debug {
+    /// sets the timeout for reads. `None` means, that reads block until data is received.
+    ///
+    /// A read, that times out, returns an error of the kind `WouldBlock` or `TimedOut`.
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()>;
+
+    /// closes the transport in both directions.
+    fn shutdown(&mut self) -> io::Result<()>;
+
+    /// returns a second handle of the same stream for writing, so one thread can block in a
+    /// read, while another one writes. Transports, that can not be shared this way, return
+    /// `None`, which is the default.
+    fn try_clone_writer(&self) -> io::Result<Option<Box<dyn Transport>>> {
+        Ok(None)
+    }
+}
+
+impl Transport for net::TcpStream {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        net::TcpStream::set_read_timeout(self, timeout)
+    }
+
+    fn shutdown(&mut self) -> io::Result<()> {
+        net::TcpStream::shutdown(self, net::Shutdown::Both)
+    }
+
+    fn try_clone_writer(&self) -> io::Result<Option<Box<dyn Transport>>> {
+        Ok(Some(Box::new(self.try_clone()?)))
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        (**self).set_read_timeout(timeout)
+    }
+
+    fn shutdown(&mut self) -> io::Result<()> {
+        (**self).shutdown()
+    }
+
+    fn try_clone_writer(&self) -> io::Result<Option<Box<dyn Transport>>> {
+        (**self).try_clone_writer()
+    }
+}
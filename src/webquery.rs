@@ -0,0 +1,648 @@
+//! The webquery module contains the WebQueryClient struct, a client for the HTTP WebQuery
+//! interface of the server (10080 by default, TS3 3.12 and newer).
+//!
+//! The WebQueryClient takes the same commands as a Connection and converts the JSON answers into
+//! the Server Query format, so the same types like ClientList and ChannelList are returned.
+//!
+//! # Example
+//! ```no_run
+//! use sqlib::webquery::WebQueryClient;
+//!
+//! let mut client = WebQueryClient::new("127.0.0.1:10080", "BAByFoiEXZfnSJyE6dbXFiW_nn_SmwkQpCf");
+//! client.use_server_id(1);
+//! println!("{}", client.clientlist().unwrap());
+//! ```
+
+use channel::ChannelList;
+use client::ClientList;
+use command::Command;
+use error;
+use error::{Error, SQError};
+use escaping::{escape, unescape};
+use map::*;
+use response::FromResponse;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net;
+use std::time::Duration;
+use textmessage::TextMessageTarget;
+
+/// The maximum size of the body of an answer in bytes. Larger answers are refused.
+pub const MAX_BODY_SIZE: u64 = 16 * 1024 * 1024;
+
+// the maximum number of nested arrays and objects in an answer
+const MAX_JSON_DEPTH: usize = 64;
+
+/// WebQueryClient sends Server Query commands to the WebQuery interface.
+///
+/// Every command is a single HTTP request, so a WebQueryClient has no session: the selected
+/// virtual server is part of the path and the API key is sent with every request.
+///
+/// # Example
+/// ```
+/// use sqlib::webquery::WebQueryClient;
+/// use std::io::{BufRead, BufReader, Write};
+/// use std::net::TcpListener;
+/// use std::thread;
+///
+/// // a stand-in for the WebQuery interface, that answers a single request
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let addr = listener.local_addr().unwrap().to_string();
+/// let server = thread::spawn(move || {
+///     let (mut stream, _) = listener.accept().unwrap();
+///     let mut request = Vec::new();
+///     for line in BufReader::new(stream.try_clone().unwrap()).lines() {
+///         let line = line.unwrap();
+///         if line.is_empty() {
+///             break;
+///         }
+///         request.push(line);
+///     }
+///     let body = r#"{"body":[{"clid":"1","cid":1,"client_database_id":"2",
+///         "client_nickname":"a\u0020b","client_type":"0"}],"status":{"code":0,"message":"ok"}}"#;
+///     write!(
+///         stream,
+///         "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+///         body.len(),
+///         body
+///     )
+///     .unwrap();
+///     request
+/// });
+///
+/// let mut client = WebQueryClient::new(&addr, "secret");
+/// client.use_server_id(1);
+/// let clients = client.clientlist().unwrap();
+///
+/// assert_eq!(clients.as_ref()[0].client_nickname, "a b");
+/// let request = server.join().unwrap();
+/// assert_eq!(request[0], "GET /1/clientlist HTTP/1.1");
+/// assert!(request.contains(&"x-api-key: secret".to_string()));
+/// ```
+#[derive(Clone)]
+pub struct WebQueryClient {
+    addr: String,
+    api_key: String,
+    sid: Option<u64>,
+    timeout: Option<Duration>,
+}
+
+impl WebQueryClient {
+    /// creates a new WebQueryClient for an address like `127.0.0.1:10080` and an API key.
+    pub fn new(addr: &str, api_key: &str) -> WebQueryClient {
+        WebQueryClient {
+            addr: addr.to_string(),
+            api_key: api_key.to_string(),
+            sid: None,
+            timeout: None,
+        }
+    }
+
+    /// selects the virtual server with the given id for the following commands.
+    pub fn use_server_id(&mut self, id: u64) {
+        self.sid = Some(id);
+    }
+
+    /// sets the timeout for connecting, reading and writing. `None` means no timeout.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// sends a given command and returns the answer in the Server Query format, or the error.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use sqlib::webquery::WebQueryClient;
+    ///
+    /// let mut client = WebQueryClient::new("127.0.0.1:10080", "secret");
+    /// client.use_server_id(1);
+    /// let answer = client.send_command(&"clientlist -uid").unwrap();
+    /// ```
+    pub fn send_command<C>(&self, command: &C) -> error::Result<String>
+    where
+        C: Command,
+    {
+        let command = command.string();
+        let mut parts = command.trim().splitn(2, char::is_whitespace);
+        let name = match parts.next() {
+            Some(name) if !name.is_empty() => name,
+            _ => return Err(Error::from("no command")),
+        };
+        let params = Record::parse(parts.next().unwrap_or(""));
+
+        let mut path = match self.sid {
+            Some(sid) => format!("/{}/{}", sid, name),
+            None => format!("/{}", name),
+        };
+        let query: Vec<String> = params
+            .iter()
+            .map(|(key, value)| match value {
+                Some(value) => format!("{}={}", url_encode(key), url_encode(&unescape(value))),
+                None => url_encode(key),
+            })
+            .collect();
+        if !query.is_empty() {
+            path.push('?');
+            path.push_str(&query.join("&"));
+        }
+
+        let (status, body) = self.get(&path)?;
+        let json = Json::parse(&body).map_err(|e| {
+            Error::Parse(format!(
+                "invalid answer with the HTTP status {}: {}",
+                status, e
+            ))
+        })?;
+        check_status(&json)?;
+        Ok(json.find("body").map(to_reply).unwrap_or_default())
+    }
+
+    /// sends a given command and parses the answer into the requested type.
+    pub fn query<T, C>(&self, command: &C) -> error::Result<T>
+    where
+        T: FromResponse,
+        C: Command,
+    {
+        let result = self.send_command(command)?;
+        T::from_response(&result)
+    }
+
+    // sends a GET request and returns the status code and the body.
+    fn get(&self, path: &str) -> error::Result<(u16, String)> {
+        let stream = match self.timeout {
+            Some(timeout) => {
+                let addr = net::ToSocketAddrs::to_socket_addrs(&self.addr.as_str())?
+                    .next()
+                    .ok_or_else(|| Error::from("the address could not be resolved"))?;
+                net::TcpStream::connect_timeout(&addr, timeout)?
+            }
+            None => net::TcpStream::connect(self.addr.as_str())?,
+        };
+        stream.set_read_timeout(self.timeout)?;
+        stream.set_write_timeout(self.timeout)?;
+
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nx-api-key: {}\r\nAccept: application/json\r\n\
+             Connection: close\r\n\r\n",
+            path, self.addr, self.api_key
+        );
+        (&stream).write_all(request.as_bytes())?;
+
+        read_response(BufReader::new(stream))
+    }
+
+    /// returns the information about the own session, like `whoami` of a Connection.
+    pub fn whoami(&self) -> error::Result<StringMap> {
+        self.query(&"whoami")
+    }
+
+    /// returns the information about the selected virtual server.
+    pub fn serverinfo(&self) -> error::Result<StringMap> {
+        self.query(&"serverinfo")
+    }
+
+    /// sends a text message to a client, the channel or the virtual server.
+    pub fn send_text_message(&self, target: &TextMessageTarget, msg: &str) -> error::Result<()> {
+        self.send_command(&target.command(msg)).map(|_| ())
+    }
+
+    /// sends the clientlist command to the server and parses the result.
+    pub fn clientlist(&self) -> error::Result<ClientList> {
+        self.query(&"clientlist")
+    }
+
+    /// like `Connection::clientlist_with_info`, it sends a clientinfo command for every client.
+    pub fn clientlist_with_info(&self) -> error::Result<ClientList> {
+        let mut clients = self.clientlist()?;
+        for client in clients.as_mut().iter_mut() {
+            let map: StringMap = self.query(&format!("clientinfo clid={}", client.clid))?;
+            client.mut_from_map(&map);
+        }
+        Ok(clients)
+    }
+
+    /// sends the channellist command to the server and parses the result.
+    pub fn channellist(&self) -> error::Result<ChannelList> {
+        self.query(&"channellist")
+    }
+
+    /// like `Connection::channellist_with_clients`.
+    pub fn channellist_with_clients(&self) -> error::Result<ChannelList> {
+        let clients = self.clientlist_with_info()?;
+        let mut channels = self.channellist()?;
+        channels.merge_clients(&clients);
+        Ok(channels)
+    }
+}
+
+impl fmt::Debug for WebQueryClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // the API key is a secret
+        f.debug_struct("WebQueryClient")
+            .field("addr", &self.addr)
+            .field("sid", &self.sid)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl fmt::Display for WebQueryClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", &self.addr)
+    }
+}
+
+// returns the error of the `status` object, if the code is not 0.
+fn check_status(json: &Json) -> error::Result<()> {
+    let status = json
+        .find("status")
+        .ok_or_else(|| Error::from("the answer has no status"))?;
+    let code = match status.find("code") {
+        Some(Json::Number(code)) | Some(Json::String(code)) => code
+            .parse()
+            .map_err(|_| Error::invalid_value("code", code))?,
+        _ => return Err(Error::from("the status has no code")),
+    };
+    if code == 0 {
+        return Ok(());
+    }
+    let msg = status
+        .find("message")
+        .and_then(|m| match *m {
+            Json::String(ref msg) => Some(msg.as_str()),
+            _ => None,
+        })
+        .unwrap_or("")
+        .to_string();
+    Err(Error::from(SQError::new(code, msg)))
+}
+
+// converts the `body` of an answer into the Server Query format.
+fn to_reply(body: &Json) -> String {
+    let records = match *body {
+        Json::Array(ref records) => records.iter().collect(),
+        ref record => vec![record],
+    };
+    records
+        .into_iter()
+        .filter_map(|record| match *record {
+            Json::Object(ref record) => Some(record),
+            _ => None,
+        })
+        .map(|record| {
+            record
+                .iter()
+                .map(|(key, value)| match *value {
+                    Json::String(ref value) => format!("{}={}", key, escape(value)),
+                    Json::Null => key.clone(),
+                    ref value => format!("{}={}", key, escape(&value.to_string())),
+                })
+                .collect::<Vec<String>>()
+                .join(" ")
+        })
+        .collect::<Vec<String>>()
+        .join("|")
+}
+
+// percent-encodes everything except the unreserved characters.
+fn url_encode(s: &str) -> String {
+    let mut encoded = String::new();
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(b as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+// reads a HTTP/1.1 response with a `Content-Length`, a chunked body or a body until EOF.
+fn read_response<R: BufRead>(mut reader: R) -> error::Result<(u16, String)> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let status = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| Error::from(format!("invalid HTTP status line {:?}", line.trim())))?;
+
+    let mut length = None;
+    let mut chunked = false;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let mut parts = header.splitn(2, ':');
+        let name = parts.next().unwrap_or("").trim().to_lowercase();
+        let value = parts.next().unwrap_or("").trim();
+        if name == "content-length" {
+            length = value.parse::<u64>().ok();
+        } else if name == "transfer-encoding" && value.to_lowercase().contains("chunked") {
+            chunked = true;
+        }
+    }
+
+    let mut body = Vec::new();
+    if chunked {
+        loop {
+            line.clear();
+            reader.read_line(&mut line)?;
+            let size = line.trim().split(';').next().unwrap_or("");
+            let size =
+                u64::from_str_radix(size, 16).map_err(|_| Error::from("invalid chunk size"))?;
+            if size == 0 {
+                break;
+            }
+            read_body(&mut reader, &mut body, size)?;
+            line.clear();
+            reader.read_line(&mut line)?;
+        }
+    } else if let Some(length) = length {
+        read_body(&mut reader, &mut body, length)?;
+    } else {
+        (&mut reader)
+            .take(MAX_BODY_SIZE + 1)
+            .read_to_end(&mut body)?;
+        if body.len() as u64 > MAX_BODY_SIZE {
+            return Err(too_large());
+        }
+    }
+
+    Ok((status, String::from_utf8_lossy(&body).into_owned()))
+}
+
+// appends `length` bytes to the body. The length is sent by the server, so the body only grows
+// with the received bytes.
+fn read_body<R: Read>(reader: &mut R, body: &mut Vec<u8>, length: u64) -> error::Result<()> {
+    match (body.len() as u64).checked_add(length) {
+        Some(total) if total <= MAX_BODY_SIZE => {}
+        _ => return Err(too_large()),
+    }
+    let read = reader.take(length).read_to_end(body)?;
+    if (read as u64) < length {
+        return Err(Error::from(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "the answer ended before its body",
+        )));
+    }
+    Ok(())
+}
+
+fn too_large() -> Error {
+    Error::from(format!("the answer is larger than {} bytes", MAX_BODY_SIZE))
+}
+
+// Json is a parsed JSON value. Numbers keep their text and objects keep the order of their keys.
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    // parses a JSON document, that contains a single value.
+    fn parse(s: &str) -> error::Result<Json> {
+        let mut parser = JsonParser {
+            chars: s.chars().peekable(),
+            depth: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        match parser.chars.next() {
+            None => Ok(value),
+            Some(c) => Err(Error::Parse(format!(
+                "unexpected {:?} after the JSON value",
+                c
+            ))),
+        }
+    }
+
+    // returns the value of a key of an object.
+    fn find(&self, key: &str) -> Option<&Json> {
+        match *self {
+            Json::Object(ref entries) => entries.iter().find(|e| e.0 == key).map(|e| &e.1),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(ref n) => write!(f, "{}", n),
+            Json::String(ref s) => write!(f, "{:?}", s),
+            Json::Array(ref values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(ref entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{:?}:{}", key, value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+struct JsonParser<'a> {
+    chars: ::std::iter::Peekable<::std::str::Chars<'a>>,
+    depth: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn skip_whitespace(&mut self) {
+        while let Some(&c) = self.chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.chars.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> error::Result<()> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(Error::Parse(format!(
+                "expected {:?} in JSON, found {:?}",
+                expected, c
+            ))),
+            None => Err(Error::Parse(format!(
+                "expected {:?} in JSON, found the end",
+                expected
+            ))),
+        }
+    }
+
+    // consumes the given keyword after its first character.
+    fn keyword(&mut self, rest: &str, value: Json) -> error::Result<Json> {
+        for expected in rest.chars() {
+            if self.chars.next() != Some(expected) {
+                return Err(Error::Parse("invalid keyword in JSON".to_string()));
+            }
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> error::Result<Json> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some('n') => self.keyword("ull", Json::Null),
+            Some('t') => self.keyword("rue", Json::Bool(true)),
+            Some('f') => self.keyword("alse", Json::Bool(false)),
+            Some('"') => self.string().map(Json::String),
+            Some('[') => self.nested(JsonParser::array),
+            Some('{') => self.nested(JsonParser::object),
+            Some(c) if c == '-' || c.is_ascii_digit() => Ok(self.number(c)),
+            Some(c) => Err(Error::Parse(format!("unexpected {:?} in JSON", c))),
+            None => Err(Error::Parse("unexpected end of JSON".to_string())),
+        }
+    }
+
+    // parses an array or an object, unless they are nested too deeply.
+    fn nested<F>(&mut self, parse: F) -> error::Result<Json>
+    where
+        F: FnOnce(&mut Self) -> error::Result<Json>,
+    {
+        if self.depth >= MAX_JSON_DEPTH {
+            return Err(Error::Parse(format!(
+                "JSON is nested deeper than {} levels",
+                MAX_JSON_DEPTH
+            )));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn number(&mut self, first: char) -> Json {
+        let mut number = first.to_string();
+        while let Some(&c) = self.chars.peek() {
+            if !(c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || c == '+' || c == '-') {
+                break;
+            }
+            number.push(c);
+            self.chars.next();
+        }
+        Json::Number(number)
+    }
+
+    // parses a string after the opening quote.
+    fn string(&mut self) -> error::Result<String> {
+        let mut s = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => match self.chars.next() {
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('u') => {
+                        let high = self.hex()?;
+                        let code = if (0xD800..0xDC00).contains(&high) {
+                            // a surrogate pair
+                            if self.chars.next() != Some('\\') || self.chars.next() != Some('u') {
+                                return Err(Error::Parse(
+                                    "invalid surrogate pair in JSON".to_string(),
+                                ));
+                            }
+                            let low = self.hex()?;
+                            0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF)
+                        } else {
+                            high
+                        };
+                        s.push(::std::char::from_u32(code).unwrap_or('\u{FFFD}'));
+                    }
+                    Some(c) => s.push(c),
+                    None => break,
+                },
+                Some(c) => s.push(c),
+                None => break,
+            }
+        }
+        Err(Error::Parse("unterminated string in JSON".to_string()))
+    }
+
+    fn hex(&mut self) -> error::Result<u32> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .chars
+                .next()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| Error::Parse("invalid unicode escape in JSON".to_string()))?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    // parses an array after the opening bracket.
+    fn array(&mut self) -> error::Result<Json> {
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&']') {
+            self.chars.next();
+            return Ok(Json::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => {}
+                Some(']') => return Ok(Json::Array(values)),
+                _ => return Err(Error::Parse("expected ',' or ']' in JSON".to_string())),
+            }
+        }
+    }
+
+    // parses an object after the opening brace.
+    fn object(&mut self) -> error::Result<Json> {
+        let mut entries = Vec::new();
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&'}') {
+            self.chars.next();
+            return Ok(Json::Object(entries));
+        }
+        loop {
+            self.expect('"')?;
+            let key = self.string()?;
+            self.expect(':')?;
+            entries.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => {}
+                Some('}') => return Ok(Json::Object(entries)),
+                _ => return Err(Error::Parse("expected ',' or '}' in JSON".to_string())),
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Tests of the AsyncConnection against the MockServer. They need the `async` feature.
+#![cfg(feature = "async")]
+
+extern crate futures_core;
+extern crate sqlib;
+extern crate tokio;
+
+use futures_core::Stream;
+use sqlib::async_connection::{AsyncConnection, Notifications};
+use sqlib::notification::Notification;
+use sqlib::testing::{MockServer, Reply};
+use std::future::poll_fn;
+use std::pin::Pin;
+use tokio::runtime::{Builder, Runtime};
+
+fn connect(server: &MockServer) -> (Runtime, AsyncConnection) {
+    let runtime = Builder::new_current_thread().enable_io().build().unwrap();
+    let conn = runtime
+        .block_on(AsyncConnection::connect(&server.addr()))
+        .unwrap();
+    (runtime, conn)
+}
+
+fn next(runtime: &Runtime, notifications: &mut Notifications) -> Option<Notification> {
+    runtime.block_on(poll_fn(|cx| Pin::new(&mut *notifications).poll_next(cx)))
+}
+
+#[test]
+fn answers_are_matched_in_order() {
+    let server = MockServer::start().unwrap();
+    server.on("whoami", Reply::ok("virtualserver_id=1"));
+    server.on("version", Reply::ok("version=3.13.7"));
+    let (runtime, conn) = connect(&server);
+
+    let whoami = conn.send_command(&"whoami");
+    let unknown = conn.send_command(&"unknown");
+    let version = conn.send_command(&"version");
+
+    // the replies are awaited in the reverse order
+    assert_eq!(runtime.block_on(version).unwrap().trim(), "version=3.13.7");
+    assert_eq!(
+        runtime.block_on(unknown).unwrap_err().to_string(),
+        "error id=256 msg=command not found"
+    );
+    assert_eq!(
+        runtime.block_on(whoami).unwrap().trim(),
+        "virtualserver_id=1"
+    );
+    assert_eq!(server.received(), vec!["whoami", "unknown", "version"]);
+}
+
+#[test]
+fn notifications_between_answer_lines_are_streamed() {
+    let server = MockServer::start().unwrap();
+    server.on(
+        "whoami",
+        Reply::ok("notifyclientmoved ctid=2 reasonid=0 clid=5\n\rvirtualserver_id=1"),
+    );
+    let (runtime, conn) = connect(&server);
+    let mut notifications = conn.notifications();
+    runtime
+        .block_on(conn.register_notifications("server", None))
+        .unwrap();
+
+    let answer = runtime.block_on(conn.send_command(&"whoami")).unwrap();
+    assert_eq!(answer.trim(), "virtualserver_id=1");
+
+    let notification = next(&runtime, &mut notifications).unwrap();
+    assert_eq!(notification.name(), "notifyclientmoved");
+    assert_eq!(notification.get("clid"), Some("5"));
+}
+
+#[test]
+fn errors_keep_their_kind() {
+    let server = MockServer::start().unwrap();
+    server.once("whoami", Reply::error(524, "client is flooding"));
+    let (runtime, conn) = connect(&server);
+
+    let err = runtime.block_on(conn.send_command(&"whoami")).unwrap_err();
+    assert!(err.is_flooding());
+}
+
+#[test]
+fn closed_connection_fails_all_waiting_commands() {
+    let server = MockServer::start().unwrap();
+    server.on("whoami", Reply::Drop);
+    let (runtime, conn) = connect(&server);
+    let mut notifications = conn.notifications();
+
+    let first = conn.send_command(&"whoami");
+    let second = conn.send_command(&"version");
+    assert!(runtime.block_on(first).unwrap_err().is_io());
+    assert!(runtime.block_on(second).unwrap_err().is_io());
+
+    // the driver is gone, so the stream ends and new commands fail
+    assert!(next(&runtime, &mut notifications).is_none());
+    let err = runtime.block_on(conn.send_command(&"version")).unwrap_err();
+    assert!(err.is_io());
+}
@@ -0,0 +1,71 @@
+//! Tests of the Connection against the MockServer.
+
+extern crate sqlib;
+
+use sqlib::connection::Connection;
+use sqlib::ratelimit::RateLimit;
+use sqlib::testing::{MockServer, Reply};
+use sqlib::transport::Transport;
+use std::io;
+use std::io::prelude::*;
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// a TCP Transport, that records the time of every flush
+#[derive(Debug)]
+struct Timed {
+    stream: TcpStream,
+    flushes: Arc<Mutex<Vec<Instant>>>,
+}
+
+impl Read for Timed {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl Write for Timed {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushes.lock().unwrap().push(Instant::now());
+        self.stream.flush()
+    }
+}
+
+impl Transport for Timed {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.stream.set_read_timeout(timeout)
+    }
+
+    fn shutdown(&mut self) -> io::Result<()> {
+        Transport::shutdown(&mut self.stream)
+    }
+}
+
+#[test]
+fn batches_are_spaced_by_the_rate_limit() {
+    let server = MockServer::start().unwrap();
+    server.on("whoami", Reply::ok("virtualserver_id=1"));
+    let flushes = Arc::new(Mutex::new(Vec::new()));
+    let transport = Timed {
+        stream: TcpStream::connect(server.addr()).unwrap(),
+        flushes: flushes.clone(),
+    };
+    let mut conn = Connection::with_transport(&server.addr(), transport).unwrap();
+    let per = Duration::from_millis(100);
+    conn.set_rate_limit(RateLimit::new(2, per));
+
+    let results = conn.send_batch(vec!["whoami"; 6]);
+
+    assert!(results.iter().all(|result| result.is_ok()));
+    let flushes = flushes.lock().unwrap();
+    assert_eq!(flushes.len(), 6);
+    // no more than 2 commands reach the server in any 100ms
+    for window in flushes.windows(3) {
+        assert!(window[2] - window[0] >= per);
+    }
+}
@@ -0,0 +1,143 @@
+//! Tests of the ConnectionPool against the MockServer.
+
+extern crate sqlib;
+
+use sqlib::error::Error;
+use sqlib::metrics::Metrics;
+use sqlib::pool::ConnectionPool;
+use sqlib::testing::{MockServer, Reply};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+fn server() -> MockServer {
+    let server = MockServer::start().unwrap();
+    server.on("whoami", Reply::ok("virtualserver_id=1 client_id=5"));
+    server.on(
+        "login serveradmin wrong",
+        Reply::error(520, "invalid loginname or password"),
+    );
+    server
+}
+
+#[test]
+fn idle_sessions_are_reused() {
+    let server = server();
+    let pool = ConnectionPool::new();
+
+    drop(
+        pool.get(&server.addr(), 1, "serveradmin", "secret")
+            .unwrap(),
+    );
+    drop(
+        pool.get(&server.addr(), 1, "serveradmin", "secret")
+            .unwrap(),
+    );
+
+    assert_eq!(server.connections(), 1);
+    assert_eq!(pool.idle_count(), 1);
+    assert!(server.received().contains(&"whoami".to_string()));
+}
+
+#[test]
+fn wrong_password_does_not_reuse_sessions() {
+    let server = server();
+    let pool = ConnectionPool::new();
+
+    drop(
+        pool.get(&server.addr(), 1, "serveradmin", "secret")
+            .unwrap(),
+    );
+    let err = pool
+        .get(&server.addr(), 1, "serveradmin", "wrong")
+        .unwrap_err();
+
+    match err {
+        Error::SQ(e) => assert_eq!(e.id(), 520),
+        e => panic!("unexpected error {}", e),
+    }
+    assert_eq!(server.connections(), 2);
+    assert_eq!(pool.idle_count(), 1);
+    assert_eq!(pool.open_count(&server.addr()), 1);
+}
+
+#[test]
+fn unhealthy_sessions_are_replaced() {
+    let server = server();
+    let metrics = Arc::new(Metrics::new());
+    let pool = ConnectionPool::new().metrics(metrics.clone());
+
+    drop(
+        pool.get(&server.addr(), 1, "serveradmin", "secret")
+            .unwrap(),
+    );
+    assert_eq!(metrics.reconnects(), 0);
+    server.once("whoami", Reply::error(1024, "invalid serverID"));
+    drop(
+        pool.get(&server.addr(), 1, "serveradmin", "secret")
+            .unwrap(),
+    );
+
+    assert_eq!(server.connections(), 2);
+    assert_eq!(pool.open_count(&server.addr()), 1);
+    assert_eq!(metrics.reconnects(), 1);
+}
+
+#[test]
+fn sessions_are_limited_per_host() {
+    let server = server();
+    let pool = ConnectionPool::new()
+        .max_per_host(1)
+        .checkout_timeout(Duration::from_millis(100));
+
+    let conn = pool
+        .get(&server.addr(), 1, "serveradmin", "secret")
+        .unwrap();
+    assert!(pool
+        .get(&server.addr(), 2, "serveradmin", "secret")
+        .is_err());
+    drop(conn);
+
+    // the idle session of the other virtual server is closed for the new one
+    pool.get(&server.addr(), 2, "serveradmin", "secret")
+        .unwrap();
+    assert_eq!(server.connections(), 2);
+    assert_eq!(pool.open_count(&server.addr()), 1);
+}
+
+#[test]
+fn waiting_get_takes_returned_session() {
+    let server = server();
+    let pool = ConnectionPool::new().max_per_host(1);
+
+    let conn = pool
+        .get(&server.addr(), 1, "serveradmin", "secret")
+        .unwrap();
+    let waiting = {
+        let pool = pool.clone();
+        let addr = server.addr();
+        thread::spawn(move || pool.get(&addr, 1, "serveradmin", "secret").is_ok())
+    };
+    thread::sleep(Duration::from_millis(50));
+    drop(conn);
+
+    assert!(waiting.join().unwrap());
+    assert_eq!(server.connections(), 1);
+}
+
+#[test]
+fn idle_sessions_are_evicted() {
+    let server = server();
+    let pool = ConnectionPool::new().idle_timeout(Duration::from_millis(20));
+
+    drop(
+        pool.get(&server.addr(), 1, "serveradmin", "secret")
+            .unwrap(),
+    );
+    assert_eq!(pool.evict_idle(), 0);
+    thread::sleep(Duration::from_millis(40));
+
+    assert_eq!(pool.evict_idle(), 1);
+    assert_eq!(pool.idle_count(), 0);
+    assert_eq!(pool.open_count(&server.addr()), 0);
+}
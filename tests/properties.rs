@@ -0,0 +1,189 @@
+//! Property-based tests of the parsers and the escaping.
+
+extern crate proptest;
+extern crate sqlib;
+
+use proptest::prelude::*;
+use sqlib::channel::{Channel, ChannelList};
+use sqlib::client::{Client, ClientList};
+use sqlib::error::SQError;
+use sqlib::escaping::{escape, unescape};
+use sqlib::map::{to_map, Record, RecordRef, ToParams};
+use sqlib::notification::Notification;
+use std::str::FromStr;
+
+// keys of the Server Query are lowercase words with underscores.
+fn key() -> impl Strategy<Value = String> {
+    "[a-z_][a-z0-9_]{0,15}"
+}
+
+// strings with many of the characters, that have to be escaped.
+fn text() -> impl Strategy<Value = String> {
+    prop_oneof![any::<String>(), "[ a-z/|\\\\\\x07-\\x0d=]{0,20}"]
+}
+
+fn client() -> impl Strategy<Value = Client> {
+    let ids = (
+        any::<i64>(),
+        any::<i64>(),
+        any::<i64>(),
+        0..2i64,
+        any::<i64>(),
+    );
+    let texts = (text(), text(), text(), text());
+    (ids, texts).prop_map(
+        |((clid, cid, client_database_id, client_type, time), (nickname, uid, away, version))| {
+            Client {
+                clid,
+                cid,
+                client_database_id,
+                client_nickname: nickname,
+                client_type,
+                connection_connected_time: time,
+                client_unique_identifier: uid,
+                client_away_message: away,
+                client_version: version,
+                ..Client::default()
+            }
+        },
+    )
+}
+
+fn channel() -> impl Strategy<Value = Channel> {
+    (any::<i64>(), any::<i64>(), any::<i64>(), text()).prop_map(
+        |(cid, pid, channel_order, channel_name)| Channel {
+            cid,
+            pid,
+            channel_order,
+            channel_name,
+            clients: Vec::new(),
+        },
+    )
+}
+
+proptest! {
+    #[test]
+    fn unescape_reverts_escape(s in text()) {
+        prop_assert_eq!(unescape(&escape(&s)), s);
+    }
+
+    #[test]
+    fn escaped_strings_have_no_seperators(s in text()) {
+        let escaped = escape(&s);
+        prop_assert!(!escaped.contains(|c: char| c.is_ascii_whitespace() || c == '|'));
+    }
+
+    #[test]
+    fn unescape_does_not_panic(s in "\\PC*") {
+        unescape(&s);
+    }
+
+    #[test]
+    fn to_map_keeps_escaped_values(pairs in prop::collection::hash_map(key(), text(), 0..8)) {
+        let line = pairs
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, escape(value)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let map = to_map(&line);
+        prop_assert_eq!(map.len(), pairs.len());
+        for (key, value) in &pairs {
+            prop_assert_eq!(&unescape(&map[key]), value);
+        }
+    }
+
+    #[test]
+    fn record_display_reverts_parse(pairs in prop::collection::vec((key(), prop::option::of(text())), 0..8)) {
+        let mut record = Record::new();
+        for (key, value) in &pairs {
+            record.push(key, value.as_ref().map(|v| escape(v)).as_deref());
+        }
+        prop_assert_eq!(Record::parse(&record.to_string()), record);
+    }
+
+    #[test]
+    fn to_map_does_not_panic(s in "\\PC*") {
+        to_map(&s);
+        Record::parse_list(&s);
+    }
+
+    #[test]
+    fn record_ref_matches_record(s in "\\PC*") {
+        prop_assert_eq!(RecordRef::parse(&s).to_record(), Record::parse(&s));
+        prop_assert_eq!(RecordRef::parse(&s).to_map(), Record::parse(&s).to_map());
+        prop_assert_eq!(RecordRef::parse_list(&s).len(), Record::parse_list(&s).len());
+    }
+
+    #[test]
+    fn sqerror_parse_reads_errors(id in any::<u32>(), msg in text()) {
+        let line = format!("error id={} msg={}", id, escape(&msg));
+        let err = SQError::parse(&line).unwrap();
+        prop_assert_eq!(err.id(), id);
+        prop_assert_eq!(err.msg(), msg);
+    }
+
+    #[test]
+    fn sqerror_parse_does_not_panic(s in "\\PC*") {
+        SQError::parse(&s);
+        let _ = SQError::parse_is_ok(&s);
+    }
+
+    #[test]
+    fn client_reverts_to_params(client in client()) {
+        let parsed = Client::from_str(&client.to_params()).unwrap();
+        prop_assert_eq!(parsed.clid, client.clid);
+        prop_assert_eq!(parsed.cid, client.cid);
+        prop_assert_eq!(parsed.client_database_id, client.client_database_id);
+        prop_assert_eq!(parsed.client_nickname, client.client_nickname);
+        prop_assert_eq!(parsed.client_type, client.client_type);
+        prop_assert_eq!(parsed.connection_connected_time, client.connection_connected_time);
+        prop_assert_eq!(parsed.client_unique_identifier, client.client_unique_identifier);
+        prop_assert_eq!(parsed.client_away_message, client.client_away_message);
+        prop_assert_eq!(parsed.client_version, client.client_version);
+    }
+
+    #[test]
+    fn clientlist_reads_all_clients(clients in prop::collection::vec(client(), 1..8)) {
+        let answer = clients.iter().map(|c| c.to_params()).collect::<Vec<_>>().join("|");
+        let parsed = ClientList::from_str(&answer).unwrap();
+        prop_assert_eq!(parsed.len(), clients.len());
+        for (parsed, client) in parsed.iter().zip(clients.iter()) {
+            prop_assert_eq!(parsed.clid, client.clid);
+            prop_assert_eq!(&parsed.client_nickname, &client.client_nickname);
+        }
+    }
+
+    #[test]
+    fn clientlist_does_not_panic(s in "\\PC*") {
+        let _ = ClientList::from_str(&s);
+    }
+
+    #[test]
+    fn channellist_reads_all_channels(channels in prop::collection::vec(channel(), 1..8)) {
+        let answer = channels.iter().map(|c| c.to_params()).collect::<Vec<_>>().join("|");
+        let parsed = ChannelList::from_str(&answer).unwrap();
+        prop_assert_eq!(parsed.len(), channels.len());
+        for (parsed, channel) in parsed.iter().zip(channels.iter()) {
+            prop_assert_eq!(parsed.cid, channel.cid);
+            prop_assert_eq!(parsed.pid, channel.pid);
+            prop_assert_eq!(parsed.channel_order, channel.channel_order);
+            prop_assert_eq!(&parsed.channel_name, &channel.channel_name);
+        }
+    }
+
+    #[test]
+    fn channellist_does_not_panic(s in "\\PC*") {
+        let _ = ChannelList::from_str(&s);
+    }
+
+    #[test]
+    fn channel_new_unescapes_the_name(cid in any::<i64>(), name in text()) {
+        prop_assert_eq!(Channel::new(cid, escape(&name)).channel_name, name);
+    }
+
+    #[test]
+    fn notification_parse_does_not_panic(s in "\\PC*") {
+        Notification::parse(&s);
+        Notification::parse(&format!("notify{}", s));
+    }
+}
@@ -0,0 +1,137 @@
+//! Property-based tests of the serde format.
+#![cfg(feature = "serde")]
+
+extern crate proptest;
+#[macro_use]
+extern crate serde;
+extern crate serde_json;
+extern crate sqlib;
+
+use proptest::prelude::*;
+use sqlib::channel::{Channel, ChannelList};
+use sqlib::client::Client;
+use sqlib::error::SQError;
+use std::collections::BTreeMap;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Record {
+    id: i64,
+    name: String,
+    flag: bool,
+    away_message: Option<String>,
+    groups: Vec<u32>,
+}
+
+fn record() -> impl Strategy<Value = Record> {
+    (
+        any::<i64>(),
+        any::<String>(),
+        any::<bool>(),
+        // `Some("")` is read back as `None`, see the de module
+        prop::option::of(".+"),
+        prop::collection::vec(any::<u32>(), 0..4),
+    )
+        .prop_map(|(id, name, flag, away_message, groups)| Record {
+            id,
+            name,
+            flag,
+            away_message,
+            groups,
+        })
+}
+
+#[test]
+fn client_json_round_trip() {
+    let client: Client = "clid=5 cid=2 client_database_id=7 client_nickname=John\\sDoe \
+                          client_type=0 client_away=1 client_away_message=brb \
+                          client_servergroups=6,8"
+        .parse()
+        .unwrap();
+
+    let json = serde_json::to_string(&client).unwrap();
+    let parsed: Client = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(format!("{:?}", parsed), format!("{:?}", client));
+    assert_eq!(parsed.client_nickname, "John Doe");
+}
+
+#[test]
+fn channel_list_json_round_trip() {
+    let mut lobby = Channel::new(1, "lobby".to_string());
+    lobby.clients.push(Client::new(5, "John".to_string()));
+    let mut games = Channel::new(2, "games".to_string());
+    games.pid = 1;
+    let channels = ChannelList::from(vec![lobby, games]);
+
+    let json = serde_json::to_string(&channels).unwrap();
+    let parsed: ChannelList = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(format!("{:?}", parsed), format!("{:?}", channels));
+    assert_eq!(parsed.as_ref()[0].clients[0].client_nickname, "John");
+}
+
+#[test]
+fn sqerror_json_round_trip() {
+    let err = SQError::new(1281, "database empty result set".to_string());
+
+    let json = serde_json::to_string(&err).unwrap();
+    assert!(!json.contains("full_msg"));
+    let parsed: SQError = serde_json::from_str(&json).unwrap();
+
+    // the full message is created again
+    assert_eq!(parsed.id(), 1281);
+    assert_eq!(parsed.to_string(), err.to_string());
+    assert_eq!(format!("{:?}", parsed), format!("{:?}", err));
+}
+
+#[test]
+fn list_items_with_a_comma_are_refused() {
+    #[derive(Serialize)]
+    struct Tags {
+        tags: Vec<String>,
+    }
+
+    let tags = Tags {
+        tags: vec!["a,b".to_string()],
+    };
+    assert!(sqlib::to_string(&tags).is_err());
+}
+
+#[test]
+fn empty_options_are_read_as_none() {
+    let record = Record {
+        id: 1,
+        name: String::new(),
+        flag: false,
+        away_message: Some(String::new()),
+        groups: Vec::new(),
+    };
+
+    let answer = sqlib::to_string(&record).unwrap();
+    assert_eq!(answer, "id=1 name= flag=0 away_message= groups=");
+    let parsed: Record = sqlib::from_str(&answer).unwrap();
+    assert_eq!(parsed.away_message, None);
+}
+
+proptest! {
+    #[test]
+    fn from_str_reverts_to_string(records in prop::collection::vec(record(), 0..8)) {
+        let answer = sqlib::to_string(&records).unwrap();
+        let parsed: Vec<Record> = sqlib::from_str(&answer).unwrap();
+        prop_assert_eq!(parsed, records);
+    }
+
+    #[test]
+    fn maps_keep_all_values(map in prop::collection::btree_map("[a-z_]{1,10}", ".+", 1..8)) {
+        let answer = sqlib::to_string(&map).unwrap();
+        let parsed: BTreeMap<String, String> = sqlib::from_str(&answer).unwrap();
+        prop_assert_eq!(parsed, map);
+    }
+
+    #[test]
+    fn from_str_does_not_panic(s in "\\PC*") {
+        let _ = sqlib::from_str::<Vec<Record>>(&s);
+        let _ = sqlib::from_str::<Vec<BTreeMap<String, String>>>(&s);
+        let _ = sqlib::from_str::<sqlib::client::ClientList>(&s);
+    }
+}
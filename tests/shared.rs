@@ -0,0 +1,177 @@
+//! Tests of the SharedConnection against the MockServer.
+
+extern crate sqlib;
+
+use sqlib::ratelimit::RateLimit;
+use sqlib::shared::{SharedConnection, READ_TIMEOUT};
+use sqlib::testing::{MockServer, Reply};
+use sqlib::transport::Transport;
+use std::io;
+use std::io::prelude::*;
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// a TCP Transport without a separate writer, like the TLS and SSH transports
+#[derive(Debug)]
+struct Unsplit(TcpStream);
+
+impl Read for Unsplit {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for Unsplit {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl Transport for Unsplit {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.0.set_read_timeout(timeout)
+    }
+
+    fn shutdown(&mut self) -> io::Result<()> {
+        Transport::shutdown(&mut self.0)
+    }
+}
+
+fn send_from_threads(conn: &SharedConnection) {
+    let threads: Vec<_> = (0..4)
+        .map(|i| {
+            let conn = conn.clone();
+            thread::spawn(move || {
+                for _ in 0..10 {
+                    let answer = conn
+                        .send_command(&format!("clientinfo clid={}", i))
+                        .unwrap();
+                    assert_eq!(answer.trim(), format!("client_nickname=client{}", i));
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
+
+fn clientinfo_server() -> MockServer {
+    let server = MockServer::start().unwrap();
+    for i in 0..4 {
+        server.on(
+            &format!("clientinfo clid={}", i),
+            Reply::ok(&format!("client_nickname=client{}", i)),
+        );
+    }
+    server
+}
+
+#[test]
+fn answers_are_matched_to_their_threads() {
+    let server = clientinfo_server();
+    let conn = SharedConnection::new(&server.addr()).unwrap();
+    conn.set_rate_limit(RateLimit::disabled());
+
+    send_from_threads(&conn);
+    assert_eq!(server.received().len(), 40);
+}
+
+#[test]
+fn transports_without_a_writer_take_turns() {
+    let server = clientinfo_server();
+    let transport = Unsplit(TcpStream::connect(server.addr()).unwrap());
+    let conn = SharedConnection::with_transport(&server.addr(), transport).unwrap();
+    conn.set_rate_limit(RateLimit::disabled());
+
+    send_from_threads(&conn);
+    assert_eq!(server.received().len(), 40);
+}
+
+#[test]
+fn commands_do_not_wait_for_the_reader() {
+    let server = MockServer::start().unwrap();
+    server.on("whoami", Reply::ok("virtualserver_id=1"));
+    let conn = SharedConnection::new(&server.addr()).unwrap();
+    conn.set_rate_limit(RateLimit::disabled());
+
+    // the reader of a TCP connection blocks on its own half and never holds up a writer
+    let start = Instant::now();
+    for _ in 0..50 {
+        conn.send_command(&"whoami").unwrap();
+    }
+    assert!(start.elapsed() < READ_TIMEOUT * 10);
+}
+
+#[test]
+fn rate_limit_is_shared_by_all_clones() {
+    let server = MockServer::start().unwrap();
+    server.on("whoami", Reply::ok("virtualserver_id=1"));
+    let conn = SharedConnection::new(&server.addr()).unwrap();
+    conn.set_rate_limit(RateLimit::new(2, Duration::from_millis(200)));
+
+    let start = Instant::now();
+    let threads: Vec<_> = (0..3)
+        .map(|_| {
+            let conn = conn.clone();
+            thread::spawn(move || {
+                conn.send_command(&"whoami").unwrap();
+                conn.send_command(&"whoami").unwrap();
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    // 6 commands with 2 per 200ms need two more periods
+    assert!(start.elapsed() >= Duration::from_millis(400));
+}
+
+#[test]
+fn flood_errors_are_retried() {
+    let server = MockServer::start().unwrap();
+    server.on("whoami", Reply::ok("virtualserver_id=1"));
+    server.once("whoami", Reply::error(524, "client is flooding"));
+    let conn = SharedConnection::new(&server.addr()).unwrap();
+    conn.set_rate_limit(RateLimit::new(10, Duration::from_millis(10)));
+
+    assert_eq!(
+        conn.send_command(&"whoami").unwrap().trim(),
+        "virtualserver_id=1"
+    );
+    assert_eq!(server.received(), vec!["whoami", "whoami"]);
+}
+
+#[test]
+fn notifications_are_sent_to_subscribers() {
+    let server = MockServer::start().unwrap();
+    server.on("whoami", Reply::ok("virtualserver_id=1"));
+    let conn = SharedConnection::new(&server.addr()).unwrap();
+    let notifications = conn.subscribe();
+    conn.register_notifications("server", None).unwrap();
+
+    server.notify("notifyclientleftview cfid=1 ctid=0 clid=5");
+    conn.send_command(&"whoami").unwrap();
+
+    let notification = notifications.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(notification.get("clid"), Some("5"));
+}
+
+#[test]
+fn closed_connection_fails_commands() {
+    let server = MockServer::start().unwrap();
+    server.on("whoami", Reply::Drop);
+    let conn = SharedConnection::new(&server.addr()).unwrap();
+    let notifications = conn.subscribe();
+
+    assert!(conn.send_command(&"whoami").is_err());
+    assert!(notifications.recv().is_err());
+    assert!(conn.is_closed());
+    assert!(conn.send_command(&"version").is_err());
+}
@@ -0,0 +1,95 @@
+//! Tests of the SSH transport. They need the `ssh` feature.
+//!
+//! There is no in-process SSH server, so only the refusal of a server without SSH runs by
+//! default. The handshake, the host key check and the read timeout are untested, unless the
+//! ignored tests are run against a real server. They read the address, login, password and
+//! SHA256 fingerprint of the SSH query port from `SQLIB_SSH_ADDR`, `SQLIB_SSH_LOGIN`,
+//! `SQLIB_SSH_PASSWORD` and `SQLIB_SSH_FINGERPRINT`:
+//!
+//! ```text
+//! cargo test --features ssh --test ssh -- --ignored
+//! ```
+#![cfg(feature = "ssh")]
+
+extern crate sqlib;
+
+use sqlib::connection::Connection;
+use sqlib::ratelimit::RateLimit;
+use sqlib::shared::SharedConnection;
+use sqlib::ssh::SshTransport;
+use sqlib::testing::MockServer;
+use std::env;
+use std::thread;
+
+const WRONG_FINGERPRINT: &str = "SHA256:AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+
+fn server() -> (String, String, String, String) {
+    let var = |name: &str| env::var(name).unwrap_or_else(|_| panic!("{} is not set", name));
+    (
+        var("SQLIB_SSH_ADDR"),
+        var("SQLIB_SSH_LOGIN"),
+        var("SQLIB_SSH_PASSWORD"),
+        var("SQLIB_SSH_FINGERPRINT"),
+    )
+}
+
+#[test]
+fn no_credentials_are_sent_to_a_plain_server() {
+    let server = MockServer::start().unwrap();
+
+    let result =
+        SshTransport::connect_pinned(&server.addr(), "serveradmin", "password", WRONG_FINGERPRINT);
+
+    assert!(result.is_err());
+    assert!(server
+        .received()
+        .iter()
+        .all(|line| !line.contains("password")));
+}
+
+#[test]
+#[ignore]
+fn pinned_connection_sends_commands() {
+    let (addr, login, password, fingerprint) = server();
+
+    let mut conn = Connection::new_ssh(&addr, &login, &password, &fingerprint).unwrap();
+    conn.use_server_id(1).unwrap();
+    conn.clientlist().unwrap();
+    conn.quit().unwrap();
+}
+
+#[test]
+#[ignore]
+fn wrong_fingerprint_is_rejected() {
+    let (addr, login, password, _) = server();
+
+    let err = Connection::new_ssh(&addr, &login, &password, WRONG_FINGERPRINT).unwrap_err();
+    assert!(err.to_string().contains("does not match the pinned key"));
+}
+
+#[test]
+#[ignore]
+fn shared_connection_sends_commands() {
+    let (addr, login, password, fingerprint) = server();
+
+    // the short read timeout of the SharedConnection must not apply to its writes
+    let transport = SshTransport::connect_pinned(&addr, &login, &password, &fingerprint).unwrap();
+    let conn = SharedConnection::with_transport(&addr, transport).unwrap();
+    conn.set_rate_limit(RateLimit::disabled());
+    conn.send_command(&"use sid=1").unwrap();
+
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let conn = conn.clone();
+            thread::spawn(move || {
+                for _ in 0..5 {
+                    conn.send_command(&"whoami").unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert!(!conn.is_closed());
+}
@@ -0,0 +1,78 @@
+//! Tests of the ServerState against the MockServer.
+
+extern crate sqlib;
+
+use sqlib::connection::Connection;
+use sqlib::state::ServerState;
+use sqlib::testing::{MockServer, Reply};
+
+const CHANNELS: &str = "cid=1 pid=0 channel_order=0 channel_name=lobby|cid=2 pid=0 \
+                        channel_order=1 channel_name=games";
+
+fn clients(clients: &[(i64, i64)]) -> Reply {
+    let records: Vec<String> = clients
+        .iter()
+        .map(|&(clid, cid)| {
+            format!(
+                "clid={} cid={} client_database_id={} client_nickname=client{} client_type=0",
+                clid, cid, clid, clid
+            )
+        })
+        .collect();
+    Reply::ok(&records.join("|"))
+}
+
+fn clids(state: &ServerState, channel: usize) -> Vec<i64> {
+    state.channels()[channel]
+        .clients
+        .iter()
+        .map(|client| client.clid)
+        .collect()
+}
+
+fn server() -> MockServer {
+    let server = MockServer::start().unwrap();
+    server.on("channellist", Reply::ok(CHANNELS));
+    server.on("clientlist", clients(&[(5, 1)]));
+    server.on("whoami", Reply::ok("virtualserver_id=1"));
+    server
+}
+
+#[test]
+fn notifications_are_applied() {
+    let server = server();
+    let mut conn = Connection::new(&server.addr()).unwrap();
+    let mut state = ServerState::load(&mut conn).unwrap();
+
+    server.notify("notifyclientmoved ctid=2 reasonid=0 clid=5");
+    conn.send_command(&"whoami").unwrap();
+
+    assert_eq!(state.update(&mut conn).unwrap(), 1);
+    assert_eq!(clids(&state, 0), Vec::<i64>::new());
+    assert_eq!(clids(&state, 1), vec![5]);
+}
+
+#[test]
+fn dropped_notifications_reload_the_state() {
+    let server = server();
+    let mut conn = Connection::new(&server.addr()).unwrap();
+    let mut state = ServerState::load(&mut conn).unwrap();
+    conn.set_notification_capacity(1);
+
+    server.on("clientlist", clients(&[(5, 2), (6, 1)]));
+    server.notify("notifyclientmoved ctid=2 reasonid=0 clid=5");
+    server.notify("notifycliententerview cfid=0 ctid=1 reasonid=0 clid=6 client_nickname=client6");
+    conn.send_command(&"whoami").unwrap();
+    assert_eq!(conn.dropped_notifications(), 1);
+
+    // the first notification is lost, so the state is loaded again
+    assert_eq!(state.update(&mut conn).unwrap(), 0);
+    assert_eq!(clids(&state, 0), vec![6]);
+    assert_eq!(clids(&state, 1), vec![5]);
+
+    // the following notifications are applied again
+    server.notify("notifyclientleftview cfid=1 ctid=0 clid=6");
+    conn.send_command(&"whoami").unwrap();
+    assert_eq!(state.update(&mut conn).unwrap(), 1);
+    assert_eq!(clids(&state, 0), Vec::<i64>::new());
+}
@@ -0,0 +1,167 @@
+//! Tests of the TLS transport against an in-process rustls server. They need the `tls` feature.
+//!
+//! The certificates in `tests/data` are a self-signed test CA, a server certificate for
+//! `localhost` and `127.0.0.1` and a client certificate, that are signed by it.
+#![cfg(feature = "tls")]
+
+extern crate rustls;
+extern crate sqlib;
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig, ServerConnection, StreamOwned};
+use sqlib::connection::Connection;
+use sqlib::testing::WELCOME;
+use sqlib::tls::TlsConfig;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread;
+
+const CA: &[u8] = include_bytes!("data/ca.pem");
+const CERT: &[u8] = include_bytes!("data/server.pem");
+const KEY: &[u8] = include_bytes!("data/server.key");
+const CLIENT_CERT: &[u8] = include_bytes!("data/client.pem");
+const CLIENT_KEY: &[u8] = include_bytes!("data/client.key");
+
+fn certs(pem: &[u8]) -> Vec<CertificateDer<'static>> {
+    CertificateDer::pem_slice_iter(pem)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap()
+}
+
+// starts a server, that accepts one connection and answers `whoami`, and returns its address.
+fn start_server() -> String {
+    start_server_with(false)
+}
+
+// starts a server like `start_server`, that requires a client certificate of the test CA, if
+// `client_auth` is set.
+fn start_server_with(client_auth: bool) -> String {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .unwrap();
+    let builder = if client_auth {
+        let mut roots = RootCertStore::empty();
+        roots.add_parsable_certificates(certs(CA));
+        let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+            .build()
+            .unwrap();
+        builder.with_client_cert_verifier(verifier)
+    } else {
+        builder.with_no_client_auth()
+    };
+    let key = PrivateKeyDer::from_pem_slice(KEY).unwrap();
+    let config = builder.with_single_cert(certs(CERT), key).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        let (sock, _) = listener.accept().unwrap();
+        let conn = ServerConnection::new(Arc::new(config)).unwrap();
+        // a rejected handshake ends the connection with an error
+        let _ = serve(StreamOwned::new(conn, sock));
+    });
+    addr
+}
+
+fn serve(
+    mut stream: StreamOwned<ServerConnection, ::std::net::TcpStream>,
+) -> ::std::io::Result<()> {
+    write!(stream, "TS3\n\r{}\n\r", WELCOME)?;
+    stream.flush()?;
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        let answer = match line.trim() {
+            "whoami" => "virtualserver_id=1 client_id=5\n\rerror id=0 msg=ok\n\r",
+            _ => "error id=256 msg=command\\snot\\sfound\n\r",
+        };
+        reader.get_mut().write_all(answer.as_bytes())?;
+        reader.get_mut().flush()?;
+        line.clear();
+    }
+    Ok(())
+}
+
+#[test]
+fn commands_are_sent_over_tls() {
+    let addr = start_server();
+    let config = TlsConfig::new().ca_pem(CA).unwrap();
+
+    let mut conn = Connection::new_tls(&addr, &config).unwrap();
+
+    assert_eq!(
+        conn.send_command(&"whoami").unwrap().trim(),
+        "virtualserver_id=1 client_id=5"
+    );
+    assert!(conn.send_command(&"unknown").is_err());
+}
+
+#[test]
+fn server_name_is_checked() {
+    let addr = start_server();
+    let config = TlsConfig::new()
+        .ca_pem(CA)
+        .unwrap()
+        .server_name("localhost");
+    assert!(Connection::new_tls(&addr, &config).is_ok());
+
+    let addr = start_server();
+    let config = TlsConfig::new()
+        .ca_pem(CA)
+        .unwrap()
+        .server_name("query.example.com");
+    let err = Connection::new_tls(&addr, &config).unwrap_err();
+    assert!(err.to_string().contains("not valid for name"), "{}", err);
+}
+
+#[test]
+fn unknown_ca_is_rejected() {
+    let addr = start_server();
+
+    // the Mozilla root certificates do not contain the test CA
+    let err = Connection::new_tls(&addr, &TlsConfig::new()).unwrap_err();
+    assert!(err.to_string().contains("UnknownIssuer"), "{}", err);
+}
+
+#[test]
+fn client_certificate_is_sent() {
+    let addr = start_server_with(true);
+    let config = TlsConfig::new()
+        .ca_pem(CA)
+        .unwrap()
+        .client_cert_pem(CLIENT_CERT, CLIENT_KEY)
+        .unwrap();
+
+    let mut conn = Connection::new_tls(&addr, &config).unwrap();
+    assert!(conn.send_command(&"whoami").is_ok());
+
+    let data = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data");
+    let addr = start_server_with(true);
+    let config = TlsConfig::new()
+        .ca_file(format!("{}/ca.pem", data))
+        .unwrap()
+        .client_cert_files(
+            format!("{}/client.pem", data),
+            format!("{}/client.key", data),
+        )
+        .unwrap();
+
+    let mut conn = Connection::new_tls(&addr, &config).unwrap();
+    assert!(conn.send_command(&"whoami").is_ok());
+}
+
+#[test]
+fn missing_client_certificate_is_rejected() {
+    let addr = start_server_with(true);
+    let config = TlsConfig::new().ca_pem(CA).unwrap();
+
+    // with TLS 1.3 the server refuses the missing certificate after the handshake of the client
+    let result =
+        Connection::new_tls(&addr, &config).and_then(|mut conn| conn.send_command(&"whoami"));
+    assert!(result.is_err());
+}
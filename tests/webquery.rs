@@ -0,0 +1,114 @@
+//! Tests of the WebQueryClient against a local HTTP stand-in.
+
+extern crate sqlib;
+
+use sqlib::error::Error;
+use sqlib::webquery::{WebQueryClient, MAX_BODY_SIZE};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
+
+const OK: &str = r#""status":{"code":0,"message":"ok"}"#;
+
+// starts a stand-in, that answers a single request with the raw response, and returns a client
+// for it.
+fn serve(response: Vec<u8>) -> WebQueryClient {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        for line in BufReader::new(stream.try_clone().unwrap()).lines() {
+            if line.unwrap().is_empty() {
+                break;
+            }
+        }
+        // the client may have closed the connection already
+        let _ = stream.write_all(&response);
+    });
+    WebQueryClient::new(&addr, "secret")
+}
+
+fn with_length(body: &str) -> Vec<u8> {
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    )
+    .into_bytes()
+}
+
+#[test]
+fn chunked_answers_are_joined() {
+    let body = format!(
+        r#"{{"body":[{{"clid":"1","client_nickname":"a b"}}],{}}}"#,
+        OK
+    );
+    let (first, second) = body.split_at(10);
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n{:x}\r\n{}\r\n0\r\n\r\n",
+        first.len(),
+        first,
+        second.len(),
+        second
+    );
+    let client = serve(response.into_bytes());
+
+    let answer = client.send_command(&"clientlist").unwrap();
+    assert_eq!(answer, "clid=1 client_nickname=a\\sb");
+}
+
+#[test]
+fn error_status_is_returned() {
+    let client = serve(with_length(
+        r#"{"status":{"code":1281,"message":"database empty result set"}}"#,
+    ));
+
+    match client.send_command(&"clientdblist").unwrap_err() {
+        Error::SQ(e) => assert_eq!(e.id(), 1281),
+        e => panic!("unexpected error {}", e),
+    }
+}
+
+#[test]
+fn huge_chunk_size_is_refused() {
+    let client = serve(
+        b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n1\r\n{\r\nffffffffffffffff\r\n"
+            .to_vec(),
+    );
+
+    let err = client.send_command(&"whoami").unwrap_err();
+    assert!(err.to_string().contains("larger than"), "{}", err);
+}
+
+#[test]
+fn huge_content_length_is_refused() {
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{{}}",
+        MAX_BODY_SIZE + 1
+    );
+    let client = serve(response.into_bytes());
+
+    let err = client.send_command(&"whoami").unwrap_err();
+    assert!(err.to_string().contains("larger than"), "{}", err);
+}
+
+#[test]
+fn truncated_body_is_an_error() {
+    let client = serve(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n{}".to_vec());
+
+    assert!(client.send_command(&"whoami").unwrap_err().is_io());
+}
+
+#[test]
+fn deeply_nested_answer_is_refused() {
+    let body = format!(
+        r#"{{"body":{}{},{}}}"#,
+        "[".repeat(100_000),
+        "]".repeat(100_000),
+        OK
+    );
+    let client = serve(with_length(&body));
+
+    let err = client.send_command(&"whoami").unwrap_err();
+    assert!(err.to_string().contains("nested"), "{}", err);
+}